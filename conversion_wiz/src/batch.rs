@@ -0,0 +1,476 @@
+//! Batch conversion of a column of a CSV or TSV table.
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::io::{Read, Write};
+
+use crate::rational::rational_to_f64;
+use crate::{
+    parse_rational, significant_figures, ConversionError, ConversionFactor, ConversionGraph, NumberFormat, Precision, RationalFactor,
+};
+
+/// Name of the column the per-row errors are written to, unless the table already has one.
+const ERROR_COLUMN: &str = "error";
+
+/// `BatchOptions` struct to describe which column to convert and how.
+/// The source unit is either fixed for the whole table (`with_from_unit`) or read from
+/// another column of each row (`with_unit_column`); when both are given, the row's unit
+/// wins and the fixed unit is used for rows where that column is empty.
+#[derive(Debug, Clone)]
+pub struct BatchOptions {
+    column: String,
+    to: String,
+    from: Option<String>,
+    unit_column: Option<String>,
+    output_column: Option<String>,
+    delimiter: u8,
+    number_format: Option<NumberFormat>,
+    input_figures: bool,
+}
+
+impl BatchOptions {
+    /// Convert the values of `column` to the unit `to`. The table is comma separated by default.
+    pub fn new(column: &str, to: &str) -> Self {
+        Self {
+            column: column.to_string(),
+            to: to.to_string(),
+            from: None,
+            unit_column: None,
+            output_column: None,
+            delimiter: b',',
+            number_format: None,
+            input_figures: false,
+        }
+    }
+
+    /// Use the same source unit for every row.
+    pub fn with_from_unit(mut self, unit: &str) -> Self {
+        self.from = Some(unit.to_string());
+        self
+    }
+
+    /// Read the source unit of each row from a column.
+    pub fn with_unit_column(mut self, column: &str) -> Self {
+        self.unit_column = Some(column.to_string());
+        self
+    }
+
+    /// Name of the added column holding the converted values, `<column>_<to>` by default.
+    pub fn with_output_column(mut self, column: &str) -> Self {
+        self.output_column = Some(column.to_string());
+        self
+    }
+
+    /// Field delimiter of the input and output, e.g. `b'\t'` for TSV.
+    pub fn with_delimiter(mut self, delimiter: u8) -> Self {
+        self.delimiter = delimiter;
+        self
+    }
+
+    /// Write the converted values with `format`, as `ConversionGraph::format_value` does.
+    /// Without one they are written in full, e.g. `3.280839895013123`.
+    pub fn with_number_format(mut self, format: NumberFormat) -> Self {
+        self.number_format = Some(format);
+        self
+    }
+
+    /// Round each converted value to the significant figures of its input value, so
+    /// `12.50` gives 4 figures.
+    pub fn with_input_figures(mut self) -> Self {
+        self.input_figures = true;
+        self
+    }
+
+    fn output_column(&self) -> String {
+        match &self.output_column {
+            Some(column) => column.clone(),
+            None => format!("{}_{}", self.column, self.to),
+        }
+    }
+}
+
+/// The conversion of one source unit of a table, looked up once.
+struct TableFactor {
+    factor: ConversionFactor,
+    /// the exact factor, in rational mode
+    rational: Option<RationalFactor>,
+}
+
+/// `RowError` struct to represent a row that could not be converted.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RowError {
+    line: u64,
+    message: String,
+}
+
+impl RowError {
+    /// The line of the input the row starts on, starting at 1 for the header.
+    pub fn line(&self) -> u64 {
+        self.line
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+/// `BatchReport` struct to summarize a batch conversion.
+#[derive(Debug, Clone, Default)]
+pub struct BatchReport {
+    rows: usize,
+    errors: Vec<RowError>,
+}
+
+impl BatchReport {
+    /// Number of data rows read, excluding the header.
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    /// Number of rows converted successfully.
+    pub fn converted(&self) -> usize {
+        self.rows - self.errors.len()
+    }
+
+    /// The rows that could not be converted, in input order.
+    pub fn errors(&self) -> &[RowError] {
+        &self.errors
+    }
+}
+
+/// `BatchError` enum to represent problems that stop a batch conversion as a whole.
+/// Problems with a single row are reported in the row instead (see `RowError`).
+#[derive(Debug)]
+pub enum BatchError {
+    /// the input could not be read or the output could not be written
+    Csv(csv::Error),
+    /// the header has no column of this name
+    MissingColumn(String),
+    /// neither a source unit nor a unit column was given
+    MissingSourceUnit,
+}
+
+impl Error for BatchError {}
+
+impl fmt::Display for BatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BatchError::Csv(e) => write!(f, "{}", e),
+            BatchError::MissingColumn(column) => write!(f, "Cannot find column '{}'", column),
+            BatchError::MissingSourceUnit => write!(f, "A source unit or a unit column is required"),
+        }
+    }
+}
+
+impl From<csv::Error> for BatchError {
+    fn from(e: csv::Error) -> Self {
+        BatchError::Csv(e)
+    }
+}
+
+impl From<std::io::Error> for BatchError {
+    fn from(e: std::io::Error) -> Self {
+        BatchError::Csv(e.into())
+    }
+}
+
+/// Batch conversions.
+impl ConversionGraph {
+    /// Convert one column of a CSV/TSV table and write the table back with two added
+    /// columns: the converted value and an `error` column (`error_2`, `error_3`, ... if
+    /// the table already has one). A row whose value or unit cannot be converted keeps an
+    /// empty result and gets the reason in its error field, and the conversion carries on
+    /// with the next row. So does a malformed row, one that is not valid UTF-8 or has
+    /// more fields than the header; its extra fields are kept after the added columns.
+    ///
+    /// Units may be compound expressions (see `convert_compound`). The conversion factor
+    /// is looked up once per distinct source unit. In rational mode each value is
+    /// converted exactly, from the decimal it is written as.
+    ///
+    /// # Error
+    ///
+    /// Error if the input cannot be read or the output written, the header is not valid
+    /// CSV, a named column is missing from it, or no source unit is given at all.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use conversion_wiz::{BatchOptions, ConversionGraph};
+    ///
+    /// let mut graph = ConversionGraph::new();
+    /// graph.add_unit("Celsius", vec!["C"], false).unwrap();
+    /// graph.add_unit("Fahrenheit", vec!["F"], false).unwrap();
+    /// graph.add_edge("C", "F", 1.8, 32.0).unwrap();
+    ///
+    /// let input = "city,temp\nOslo,-5\nCairo,hot\n";
+    /// let mut output = Vec::new();
+    /// let options = BatchOptions::new("temp", "F").with_from_unit("C");
+    /// let report = graph.convert_table(input.as_bytes(), &mut output, &options).unwrap();
+    /// assert_eq!(report.converted(), 1);
+    /// assert_eq!(
+    ///     String::from_utf8(output).unwrap(),
+    ///     "city,temp,temp_F,error\nOslo,-5,23,\nCairo,hot,,Invalid number 'hot'\n"
+    /// );
+    /// ```
+    pub fn convert_table<R: Read, W: Write>(&self, input: R, output: W, options: &BatchOptions) -> Result<BatchReport, BatchError> {
+        if options.from.is_none() && options.unit_column.is_none() {
+            return Err(BatchError::MissingSourceUnit);
+        }
+        let mut reader = csv::ReaderBuilder::new()
+            .delimiter(options.delimiter)
+            .flexible(true)
+            .from_reader(input);
+        let mut writer = csv::WriterBuilder::new()
+            .delimiter(options.delimiter)
+            .flexible(true)
+            .from_writer(output);
+
+        let mut header = reader.headers()?.clone();
+        let find_column = |column: &str| header.iter()
+            .position(|h| h.trim() == column)
+            .ok_or_else(|| BatchError::MissingColumn(column.to_string()));
+        let value_index = find_column(&options.column)?;
+        let unit_index = options.unit_column.as_deref().map(find_column).transpose()?;
+        let width = header.len();
+        let output_column = options.output_column();
+        let error_column = unique_column(&header, &output_column, ERROR_COLUMN);
+        header.push_field(&output_column);
+        header.push_field(&error_column);
+        writer.write_record(&header)?;
+
+        let number_format = (options.number_format.is_some() || options.input_figures)
+            .then(|| self.unit_format(&options.to, options.number_format.clone().unwrap_or_default()));
+        let mut factors: HashMap<String, Result<TableFactor, String>> = HashMap::new();
+        let mut report = BatchReport::default();
+        // rows are read as bytes and the reader is flexible, so only I/O errors stop the
+        // batch; a row that is too long or not valid UTF-8 is written back with its error
+        for record in reader.byte_records() {
+            let mut record = record?;
+            let line = record.position().map_or(0, |p| p.line());
+            // pad short rows so the added columns stay aligned
+            while record.len() < width {
+                record.push_field(b"");
+            }
+            let checked = match record.len() {
+                len if len > width => Err((record, format!("Row has {} fields, but the header has {}", len, width))),
+                _ => csv::StringRecord::from_byte_record(record).map_err(|e| {
+                    let message = format!("Invalid UTF-8 in field {}", e.utf8_error().field() + 1);
+                    (e.into_byte_record(), message)
+                }),
+            };
+            let mut record = match checked {
+                Ok(record) => record,
+                Err((record, message)) => {
+                    // extra fields go after the added columns
+                    let mut row: csv::ByteRecord = record.iter().take(width).collect();
+                    row.push_field(b"");
+                    row.push_field(message.as_bytes());
+                    row.extend(record.iter().skip(width));
+                    writer.write_byte_record(&row)?;
+                    report.rows += 1;
+                    report.errors.push(RowError { line, message });
+                    continue;
+                }
+            };
+
+            let unit = unit_index
+                .map(|i| record[i].trim())
+                .filter(|unit| !unit.is_empty())
+                .or(options.from.as_deref());
+            let converted = match unit {
+                Some(unit) => {
+                    let factor = factors
+                        .entry(unit.to_string())
+                        .or_insert_with(|| self.table_factor(unit, &options.to));
+                    let value = record[value_index].trim();
+                    match (factor, value.parse::<f64>()) {
+                        (Err(e), _) => Err(e.clone()),
+                        (_, Err(_)) if value.is_empty() => Err("Missing value".to_string()),
+                        (_, Err(_)) => Err(format!("Invalid number '{}'", value)),
+                        (Ok(factor), Ok(number)) => Ok(factor.write(value, number, number_format.as_ref(), options.input_figures)),
+                    }
+                }
+                None => Err("Missing unit".to_string()),
+            };
+
+            report.rows += 1;
+            match converted {
+                Ok(value) => {
+                    record.push_field(&value);
+                    record.push_field("");
+                }
+                Err(message) => {
+                    record.push_field("");
+                    record.push_field(&message);
+                    report.errors.push(RowError { line, message });
+                }
+            }
+            writer.write_record(&record)?;
+        }
+        writer.flush()?;
+        Ok(report)
+    }
+
+    fn table_factor(&self, from: &str, to: &str) -> Result<TableFactor, String> {
+        let lookup = || -> Result<TableFactor, ConversionError> {
+            let (from, to) = (self.unit_expr(from)?, self.unit_expr(to)?);
+            let factor = self.factor_between_exprs(&from, &to)?;
+            let rational = match (from.as_atom(), to.as_atom()) {
+                _ if !self.rational_mode => None,
+                (Some(from), Some(to)) => Some(self.rational_factor_between(from, to)?),
+                // compound expressions only have float factors
+                _ => Some(RationalFactor::from_factor(&factor)),
+            };
+            Ok(TableFactor { factor, rational })
+        };
+        lookup().map_err(|e| self.with_suggestions(e).to_string())
+    }
+}
+
+impl TableFactor {
+    /// Convert the value written as `text`, which reads as `number`, and write the result.
+    fn write(&self, text: &str, number: f64, format: Option<&NumberFormat>, input_figures: bool) -> String {
+        let exact = self.rational.as_ref().zip(parse_rational(text)).map(|(factor, value)| factor.apply(&value));
+        let Some(format) = format else {
+            return exact.map_or(self.factor.apply(number), |exact| rational_to_f64(&exact)).to_string();
+        };
+        let format = match significant_figures(text).filter(|_| input_figures) {
+            Some(figures) => format.clone().with_precision(Precision::SignificantFigures(figures)),
+            None => format.clone(),
+        };
+        match exact {
+            Some(exact) => format.format_rational(&exact),
+            None => format.format(self.factor.apply(number)),
+        }
+    }
+}
+
+/// `name`, or `name_2`, `name_3`, ... if the header or the output column already has it.
+fn unique_column(header: &csv::StringRecord, output_column: &str, name: &str) -> String {
+    let taken = |column: &str| column == output_column || header.iter().any(|h| h.trim() == column);
+    (1..)
+        .map(|n| if n == 1 { name.to_string() } else { format!("{}_{}", name, n) })
+        .find(|column| !taken(column))
+        .expect("some column name is free")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn length_graph() -> ConversionGraph {
+        let mut graph = ConversionGraph::new();
+        let _ = graph.add_unit("meter", vec!["m"], false);
+        let _ = graph.add_unit("foot", vec!["ft"], false);
+        let _ = graph.add_unit("inch", vec!["in"], false);
+        let _ = graph.add_unit("second", vec!["s"], false);
+        let _ = graph.add_edge("ft", "m", 0.3048, 0.0);
+        let _ = graph.add_edge("ft", "in", 12.0, 0.0);
+        graph
+    }
+
+    fn run(graph: &ConversionGraph, input: &str, options: &BatchOptions) -> (String, BatchReport) {
+        let mut output = Vec::new();
+        let report = graph.convert_table(input.as_bytes(), &mut output, options).unwrap();
+        (String::from_utf8(output).unwrap(), report)
+    }
+
+    #[test]
+    fn test_convert_table_fixed_unit() {
+        let options = BatchOptions::new("length", "in").with_from_unit("ft");
+        let (output, report) = run(&length_graph(), "id,length\n1,1\n2,2.5\n", &options);
+        assert_eq!(output, "id,length,length_in,error\n1,1,12,\n2,2.5,30,\n");
+        assert_eq!((report.rows(), report.converted()), (2, 2));
+    }
+
+    #[test]
+    fn test_convert_table_unit_column_with_row_errors() {
+        let options = BatchOptions::new("value", "in").with_unit_column("unit").with_output_column("inches");
+        let input = "value,unit\n1,ft\nx,ft\n3,s\n4,\n5,parsec\n";
+        let (output, report) = run(&length_graph(), input, &options);
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines[0], "value,unit,inches,error");
+        assert_eq!(lines[1], "1,ft,12,");
+        assert_eq!(lines[2], "x,ft,,Invalid number 'x'");
+        assert!(lines[3].starts_with("3,s,,No conversion path"));
+        assert_eq!(lines[4], "4,,,Missing unit");
+        assert_eq!(lines[5], "5,parsec,,Cannot find unit parsec");
+        assert_eq!(report.converted(), 1);
+        let error_lines: Vec<u64> = report.errors().iter().map(|e| e.line()).collect();
+        assert_eq!(error_lines, vec![3, 4, 5, 6]);
+    }
+
+    #[test]
+    fn test_convert_table_tsv_and_short_rows() {
+        let options = BatchOptions::new("ft", "m").with_from_unit("ft").with_delimiter(b'\t');
+        let (output, report) = run(&length_graph(), "name\tft\nshort\n", &options);
+        assert_eq!(output, "name\tft\tft_m\terror\nshort\t\t\tMissing value\n");
+        assert_eq!(report.errors().len(), 1);
+    }
+
+    #[test]
+    fn test_convert_table_malformed_row() {
+        let options = BatchOptions::new("ft", "in").with_from_unit("ft");
+        let input = b"id,ft\n1,1\n2,\xff2\n3,3,extra\n4,4\n";
+        let mut output = Vec::new();
+        let report = length_graph().convert_table(&input[..], &mut output, &options).unwrap();
+        assert_eq!(
+            output,
+            b"id,ft,ft_in,error\n1,1,12,\n2,\xff2,,Invalid UTF-8 in field 2\n3,3,,\"Row has 3 fields, but the header has 2\",extra\n4,4,48,\n",
+        );
+        assert_eq!((report.rows(), report.converted()), (4, 2));
+        let error_lines: Vec<u64> = report.errors().iter().map(|e| e.line()).collect();
+        assert_eq!(error_lines, vec![3, 4]);
+    }
+
+    #[test]
+    fn test_convert_table_number_format() {
+        let graph = length_graph();
+        let options = BatchOptions::new("length", "ft").with_from_unit("m");
+        let (output, _) = run(&graph, "length\n1\n", &options);
+        assert_eq!(output, "length,length_ft,error\n1,3.280839895013123,\n");
+        let options = options.with_number_format(NumberFormat::new().with_precision(Precision::Decimals(2)));
+        let (output, _) = run(&graph, "length\n1\n", &options);
+        assert_eq!(output, "length,length_ft,error\n1,3.28,\n");
+        let options = BatchOptions::new("length", "ft").with_from_unit("m").with_input_figures();
+        let (output, _) = run(&graph, "length\n1.0\n12.50\n", &options);
+        assert_eq!(output, "length,length_ft,error\n1.0,3.3,\n12.50,41.01,\n");
+    }
+
+    #[test]
+    fn test_convert_table_rational_mode() {
+        let mut graph = ConversionGraph::new();
+        graph.set_rational_mode(true);
+        let _ = graph.add_unit("Celsius", vec!["C"], false);
+        let _ = graph.add_unit("Fahrenheit", vec!["F"], false);
+        let _ = graph.add_edge("C", "F", 1.8, 32.0);
+        let options = BatchOptions::new("temp", "C").with_from_unit("F");
+        let (output, _) = run(&graph, "temp\n98.6\n", &options);
+        assert_eq!(output, "temp,temp_C,error\n98.6,37,\n");
+        let options = options.with_number_format(NumberFormat::new().with_precision(Precision::Decimals(20)));
+        let (output, _) = run(&graph, "temp\n98.6\n", &options);
+        assert_eq!(output, "temp,temp_C,error\n98.6,37.00000000000000000000,\n");
+    }
+
+    #[test]
+    fn test_convert_table_existing_error_column() {
+        let options = BatchOptions::new("length", "in").with_from_unit("ft");
+        let (output, _) = run(&length_graph(), "length,error,error_2\nx,a,b\n", &options);
+        assert_eq!(output, "length,error,error_2,length_in,error_3\nx,a,b,,Invalid number 'x'\n");
+        let options = options.with_output_column("error");
+        let (output, _) = run(&length_graph(), "length\n1\n", &options);
+        assert_eq!(output, "length,error,error_2\n1,12,\n");
+    }
+
+    #[test]
+    fn test_convert_table_missing_column() {
+        let graph = length_graph();
+        let options = BatchOptions::new("depth", "m").with_from_unit("ft");
+        let result = graph.convert_table("length\n1\n".as_bytes(), Vec::new(), &options);
+        assert!(matches!(result, Err(BatchError::MissingColumn(column)) if column == "depth"));
+        let options = BatchOptions::new("length", "m");
+        let result = graph.convert_table("length\n1\n".as_bytes(), Vec::new(), &options);
+        assert!(matches!(result, Err(BatchError::MissingSourceUnit)));
+    }
+}
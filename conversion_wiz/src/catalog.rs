@@ -0,0 +1,365 @@
+//! Catalogs made of several config files, layered on top of each other.
+
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::{Config, ConfigError, ConfigFormat, Conversion};
+
+/// Where an entry of the merged config was declared.
+#[derive(Debug, Clone, PartialEq)]
+struct Origin {
+    file: PathBuf,
+    /// JSON path of the entry inside its file, e.g. `$.units[2]`
+    path: String,
+}
+
+/// `CatalogError` enum to represent a catalog whose files cannot be read or combined.
+#[derive(Debug)]
+pub enum CatalogError {
+    /// a file could not be read
+    Read(PathBuf, String),
+    /// a file is not a valid config
+    Parse(PathBuf, ConfigError),
+    /// a file includes itself, directly or through other files
+    IncludeCycle(PathBuf),
+    /// a unit is defined in two files without `override`
+    DuplicateUnit {
+        name: String,
+        first: PathBuf,
+        second: PathBuf,
+    },
+    /// units from two files share an alias
+    ConflictingAlias {
+        alias: String,
+        first_unit: String,
+        first: PathBuf,
+        second_unit: String,
+        second: PathBuf,
+    },
+}
+
+impl Error for CatalogError {}
+
+impl fmt::Display for CatalogError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CatalogError::Read(path, message) => write!(f, "Unable to read {}: {}", path.display(), message),
+            CatalogError::Parse(path, e) => write!(f, "{}: {}", path.display(), e),
+            CatalogError::IncludeCycle(path) => write!(f, "{} includes itself", path.display()),
+            CatalogError::DuplicateUnit { name, first, second } => write!(
+                f,
+                "{}: unit {} is already defined in {} (set \"override\": true to replace it)",
+                second.display(), name, first.display()
+            ),
+            CatalogError::ConflictingAlias { alias, first_unit, first, second_unit, second } => write!(
+                f,
+                "{}: alias {} of unit {} is already used by unit {} from {}",
+                second.display(), alias, second_unit, first_unit, first.display()
+            ),
+        }
+    }
+}
+
+/// `CatalogLoader` struct to merge config files into one `Config`.
+///
+/// Files are merged in the order they are loaded. The files named in a config's
+/// `include` (or `extends`) list are merged before the config itself, with paths relative
+/// to the including file; a file reached twice is merged once. The rules between files:
+///
+/// * a unit defined again in a later file is an error, unless the later entry sets
+///   `"override": true`; it then replaces the earlier definition, aliases included
+/// * an alias used by units from two different files is an error
+/// * conversions are all kept, in load order; a repeated conversion is handled by the
+///   graph's `DuplicateEdgePolicy` when the merged config is loaded
+///
+/// Problems inside a single file are left to `ConversionGraph::load_config` and
+/// `ConfigValidator`, as for a config loaded on its own.
+#[derive(Debug, Clone, Default)]
+pub struct CatalogLoader {
+    /// the merged config; all conversions are kept in `conversions`, in load order
+    config: Config,
+    /// origin of each entry of `config.units`
+    unit_origins: Vec<Origin>,
+    /// origin of each entry of `config.conversions`
+    conversion_origins: Vec<Origin>,
+    /// index in `config.units` of each unit name
+    unit_index: HashMap<String, usize>,
+    /// unit and file owning each alias (unit names included)
+    alias_owners: HashMap<String, (String, PathBuf)>,
+    /// canonical paths of the files merged so far
+    loaded: HashSet<PathBuf>,
+}
+
+impl CatalogLoader {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Merge a config file and everything it includes. The format is taken from the
+    /// file extension, defaulting to JSON.
+    ///
+    /// # Error
+    ///
+    /// Error if a file cannot be read or parsed, includes itself, or conflicts with the
+    /// files merged before it. Entries merged before the error are kept.
+    pub fn load_file(&mut self, path: impl AsRef<Path>) -> Result<(), CatalogError> {
+        self.load(path.as_ref(), None, &mut Vec::new())
+    }
+
+    /// Same as `load_file`, but reads the file (not its includes) in the given format.
+    pub fn load_file_as(&mut self, path: impl AsRef<Path>, format: ConfigFormat) -> Result<(), CatalogError> {
+        self.load(path.as_ref(), Some(format), &mut Vec::new())
+    }
+
+    /// Merge a config that is not read from a file, such as an embedded catalog.
+    /// `source` names it in errors and `source_of`, and includes are resolved relative to it.
+    ///
+    /// # Error
+    ///
+    /// Error if an include cannot be loaded or the config conflicts with the files
+    /// merged before it.
+    pub fn add_config(&mut self, config: Config, source: impl AsRef<Path>) -> Result<(), CatalogError> {
+        self.add_layer(config, source.as_ref(), &mut Vec::new())
+    }
+
+    /// The merged config, ready for `ConversionGraph::from_config`.
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// The file a unit was defined in (for an overridden unit, the file overriding it).
+    pub fn source_of(&self, unit_name: &str) -> Option<&Path> {
+        self.unit_index.get(unit_name).map(|&i| self.unit_origins[i].file.as_path())
+    }
+
+    /// Map a JSON path into the merged config, such as a `Diagnostic` path, to the file
+    /// and the path inside that file, e.g. `$.conversions[7].to` to
+    /// (`units/imperial.json`, `$.conversions_scale[2].to`).
+    pub fn locate(&self, path: &str) -> Option<(&Path, String)> {
+        let rest = path.strip_prefix("$.")?;
+        let (list, rest) = rest.split_once('[')?;
+        let (index, rest) = rest.split_once(']')?;
+        let index: usize = index.parse().ok()?;
+        let origin = match list {
+            "units" => self.unit_origins.get(index)?,
+            "conversions" => self.conversion_origins.get(index)?,
+            _ => return None,
+        };
+        Some((origin.file.as_path(), format!("{}{}", origin.path, rest)))
+    }
+
+    fn load(&mut self, path: &Path, format: Option<ConfigFormat>, stack: &mut Vec<PathBuf>) -> Result<(), CatalogError> {
+        let read_error = |e: std::io::Error| CatalogError::Read(path.to_path_buf(), e.to_string());
+        let canonical = fs::canonicalize(path).map_err(read_error)?;
+        if stack.contains(&canonical) {
+            return Err(CatalogError::IncludeCycle(path.to_path_buf()));
+        }
+        if self.loaded.contains(&canonical) {
+            return Ok(());
+        }
+
+        let text = fs::read_to_string(path).map_err(read_error)?;
+        let format = format.or_else(|| ConfigFormat::from_path(path)).unwrap_or(ConfigFormat::Json);
+        let config = Config::parse(&text, format).map_err(|e| CatalogError::Parse(path.to_path_buf(), e))?;
+
+        stack.push(canonical.clone());
+        let added = self.add_layer(config, path, stack);
+        stack.pop();
+        self.loaded.insert(canonical);
+        added
+    }
+
+    fn add_layer(&mut self, config: Config, file: &Path, stack: &mut Vec<PathBuf>) -> Result<(), CatalogError> {
+        let directory = file.parent().unwrap_or(Path::new(""));
+        for include in &config.include {
+            self.load(&directory.join(include), None, stack)?;
+        }
+
+        // every conversion becomes a general one, so the merged order is the load order
+        let conversions: Vec<(Conversion, String)> = config.edges().into_iter()
+            .map(|edge| {
+                let conversion = Conversion {
+                    from: edge.from.to_string(),
+                    to: edge.to.to_string(),
+                    factor: edge.factor,
+                    offset: edge.offset,
+                    exact: edge.exact,
+                };
+                (conversion, edge.path)
+            })
+            .collect();
+
+        for (i, mut unit) in config.units.into_iter().enumerate() {
+            let origin = Origin { file: file.to_path_buf(), path: format!("$.units[{}]", i) };
+            let replaced = match self.unit_index.get(&unit.name) {
+                Some(&index) if self.unit_origins[index].file != file => {
+                    if !unit.overrides {
+                        return Err(CatalogError::DuplicateUnit {
+                            name: unit.name,
+                            first: self.unit_origins[index].file.clone(),
+                            second: file.to_path_buf(),
+                        });
+                    }
+                    self.alias_owners.retain(|_, (owner, _)| *owner != unit.name);
+                    Some(index)
+                }
+                _ => None,
+            };
+
+            for alias in unit.aliases.iter().chain([&unit.name]) {
+                match self.alias_owners.get(alias) {
+                    Some((owner, owner_file)) if *owner != unit.name && owner_file != file => {
+                        return Err(CatalogError::ConflictingAlias {
+                            alias: alias.clone(),
+                            first_unit: owner.clone(),
+                            first: owner_file.clone(),
+                            second_unit: unit.name.clone(),
+                            second: file.to_path_buf(),
+                        });
+                    }
+                    Some(_) => {}
+                    None => {
+                        self.alias_owners.insert(alias.clone(), (unit.name.clone(), file.to_path_buf()));
+                    }
+                }
+            }
+
+            unit.overrides = false;
+            match replaced {
+                Some(index) => {
+                    self.config.units[index] = unit;
+                    self.unit_origins[index] = origin;
+                }
+                None => {
+                    self.unit_index.entry(unit.name.clone()).or_insert(self.config.units.len());
+                    self.config.units.push(unit);
+                    self.unit_origins.push(origin);
+                }
+            }
+        }
+
+        for (conversion, path) in conversions {
+            self.config.conversions.push(conversion);
+            self.conversion_origins.push(Origin { file: file.to_path_buf(), path });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ConversionGraph;
+    use approx::assert_relative_eq;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    const REL_TOL: f64 = 1e-9;
+
+    /// A temporary directory, removed with everything in it when dropped.
+    struct CatalogDir(PathBuf);
+
+    impl CatalogDir {
+        fn join(&self, file: &str) -> PathBuf {
+            self.0.join(file)
+        }
+    }
+
+    impl Drop for CatalogDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    /// A fresh directory with the given files in it, named after the test, the process
+    /// and a counter so no two directories clash.
+    fn catalog_dir(name: &str, files: &[(&str, &str)]) -> CatalogDir {
+        static COUNT: AtomicUsize = AtomicUsize::new(0);
+        let count = COUNT.fetch_add(1, Ordering::Relaxed);
+        let dir = CatalogDir(std::env::temp_dir().join(format!("conversion_wiz_catalog_{}_{}_{}", name, std::process::id(), count)));
+        let _ = fs::remove_dir_all(&dir.0);
+        fs::create_dir_all(dir.join("teams")).unwrap();
+        for (file, content) in files {
+            fs::write(dir.join(file), content).unwrap();
+        }
+        dir
+    }
+
+    const BASE: &str = r#"{
+        "units": [
+            { "name": "meter", "aliases": ["m"], "intermediate": false, "dimension": "length" },
+            { "name": "foot", "aliases": ["ft"], "intermediate": false, "dimension": "length" }
+        ],
+        "conversions_scale": [{ "from": "ft", "to": "m", "factor": 0.3048 }]
+    }"#;
+
+    #[test]
+    fn test_catalog_include_and_sources() {
+        let dir = catalog_dir("include", &[
+            ("base.json", BASE),
+            ("teams/survey.yaml", "include: [../base.json]\nunits:\n  - { name: survey foot, aliases: [ftUS], intermediate: false }\nconversions:\n  - { from: ftUS, to: m, factor: 0.3048006096 }\n"),
+        ]);
+        let mut loader = CatalogLoader::new();
+        loader.load_file(dir.join("teams/survey.yaml")).unwrap();
+        // loading the base again is a no-op
+        loader.load_file(dir.join("base.json")).unwrap();
+
+        let graph = ConversionGraph::from_config(loader.config()).unwrap();
+        assert_relative_eq!(graph.convert("ftUS", "ft", 1.0).unwrap(), 1.000002, max_relative = 1e-6);
+        assert!(loader.source_of("meter").unwrap().ends_with("base.json"));
+        assert!(loader.source_of("survey foot").unwrap().ends_with("survey.yaml"));
+        let (file, path) = loader.locate("$.conversions[1].to").unwrap();
+        assert!(file.ends_with("survey.yaml"));
+        assert_eq!(path, "$.conversions[0].to");
+        let (file, path) = loader.locate("$.conversions[0]").unwrap();
+        assert!(file.ends_with("base.json"));
+        assert_eq!(path, "$.conversions_scale[0]");
+    }
+
+    #[test]
+    fn test_catalog_override_rules() {
+        let dir = catalog_dir("override", &[
+            ("base.json", BASE),
+            ("redefine.json", r#"{ "units": [{ "name": "foot", "aliases": ["ft"], "intermediate": false }] }"#),
+            ("override.json", r#"{ "extends": ["base.json"], "units": [{ "name": "foot", "aliases": ["ft", "feet"], "intermediate": false, "override": true }] }"#),
+        ]);
+        let mut loader = CatalogLoader::new();
+        loader.load_file(dir.join("base.json")).unwrap();
+        let result = loader.load_file(dir.join("redefine.json"));
+        assert!(matches!(result, Err(CatalogError::DuplicateUnit { name, .. }) if name == "foot"));
+
+        let mut loader = CatalogLoader::new();
+        loader.load_file(dir.join("override.json")).unwrap();
+        assert_eq!(loader.config().units.len(), 2);
+        assert!(loader.source_of("foot").unwrap().ends_with("override.json"));
+        let graph = ConversionGraph::from_config(loader.config()).unwrap();
+        assert_relative_eq!(graph.convert("feet", "m", 10.0).unwrap(), 3.048, max_relative = REL_TOL);
+        // the override drops the dimension of the old definition
+        assert_eq!(graph.unit("feet").unwrap().dimension(), None);
+    }
+
+    #[test]
+    fn test_catalog_conflicting_alias() {
+        let dir = catalog_dir("alias", &[
+            ("base.json", BASE),
+            ("minutes.toml", "[[units]]\nname = \"minute\"\naliases = [\"m\"]\nintermediate = false\n"),
+        ]);
+        let mut loader = CatalogLoader::new();
+        loader.load_file(dir.join("base.json")).unwrap();
+        let error = loader.load_file(dir.join("minutes.toml")).unwrap_err();
+        assert!(matches!(&error, CatalogError::ConflictingAlias { alias, first_unit, .. } if alias == "m" && first_unit == "meter"));
+        assert!(error.to_string().contains("base.json"));
+    }
+
+    #[test]
+    fn test_catalog_include_cycle() {
+        let dir = catalog_dir("cycle", &[
+            ("a.json", r#"{ "include": ["b.json"], "units": [] }"#),
+            ("b.json", r#"{ "include": ["a.json"], "units": [] }"#),
+        ]);
+        let result = CatalogLoader::new().load_file(dir.join("a.json"));
+        assert!(matches!(result, Err(CatalogError::IncludeCycle(path)) if path.ends_with("a.json")));
+    }
+}
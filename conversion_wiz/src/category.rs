@@ -0,0 +1,80 @@
+//! Grouping units by category, the kind of quantity they measure.
+
+use std::collections::BTreeMap;
+
+use crate::{ConversionGraph, Unit};
+
+/// The category of units that do not declare one.
+pub const UNCATEGORIZED: &str = "uncategorized";
+
+/// Unit categories.
+impl ConversionGraph {
+    /// The visible units grouped by category, with the categories and the units of each
+    /// sorted by name. Units without a category are grouped under `UNCATEGORIZED`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use conversion_wiz::{ConversionGraph, Unit, UNCATEGORIZED};
+    ///
+    /// let mut graph = ConversionGraph::new();
+    /// graph.insert_unit(Unit::new("meter", vec!["m"], false).unwrap().with_category("length")).unwrap();
+    /// graph.insert_unit(Unit::new("foot", vec!["ft"], false).unwrap().with_category("length")).unwrap();
+    /// graph.add_unit("widget", vec![], false).unwrap();
+    /// let groups = graph.units_by_category();
+    /// let lengths: Vec<&str> = groups["length"].iter().map(|unit| unit.name()).collect();
+    /// assert_eq!(lengths, vec!["foot", "meter"]);
+    /// assert_eq!(groups[UNCATEGORIZED].len(), 1);
+    /// ```
+    pub fn units_by_category(&self) -> BTreeMap<&str, Vec<&Unit>> {
+        let mut groups: BTreeMap<&str, Vec<&Unit>> = BTreeMap::new();
+        for unit in self.units().into_iter().filter(|unit| !unit.intermediate()) {
+            groups.entry(unit.category().unwrap_or(UNCATEGORIZED)).or_default().push(unit);
+        }
+        groups
+    }
+
+    /// The visible units of one category, sorted by name. The category is matched
+    /// regardless of case; an unknown category has no units.
+    pub fn units_in_category(&self, category: &str) -> Vec<&Unit> {
+        self.units_by_category()
+            .into_iter()
+            .filter(|(name, _)| name.eq_ignore_ascii_case(category))
+            .flat_map(|(_, units)| units)
+            .collect()
+    }
+
+    /// Every category of the visible units, sorted.
+    pub fn categories(&self) -> Vec<&str> {
+        self.units_by_category().into_keys().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Config;
+
+    #[test]
+    fn test_units_by_category() {
+        let graph = ConversionGraph::from_config(&Config::standard()).unwrap();
+        let groups = graph.units_by_category();
+        assert!(!groups.contains_key(UNCATEGORIZED));
+        let temperatures: Vec<&str> = groups["temperature"].iter().map(|unit| unit.name()).collect();
+        assert_eq!(temperatures, vec!["Celsius", "Fahrenheit", "Rankine", "kelvin"]);
+        assert!(groups.values().flatten().all(|unit| !unit.intermediate()));
+        assert_eq!(graph.categories().len(), groups.len());
+        assert_eq!(graph.units_in_category("Temperature").len(), 4);
+        assert!(graph.units_in_category("flavor").is_empty());
+    }
+
+    #[test]
+    fn test_uncategorized_and_intermediate_units() {
+        let mut graph = ConversionGraph::new();
+        graph.insert_unit(Unit::new("hidden", vec![], true).unwrap().with_category("length")).unwrap();
+        graph.add_unit("widget", vec!["w"], false).unwrap();
+        let groups = graph.units_by_category();
+        assert_eq!(groups.keys().copied().collect::<Vec<_>>(), vec![UNCATEGORIZED]);
+        assert_eq!(groups[UNCATEGORIZED][0].name(), "widget");
+    }
+}
@@ -0,0 +1,254 @@
+//! Compiled, read-only form of a `ConversionGraph`.
+
+use std::collections::HashMap;
+
+use crate::lookup::{suggestions, AliasTable};
+use crate::prefix::split_prefix;
+use crate::{ConversionError, ConversionFactor, ConversionGraph, Dimension, PrefixKind, Unit, UnitLookup};
+
+/// Position of a unit inside a `CompiledGraph`.
+#[derive(Debug, Clone, Copy)]
+struct CompiledUnit {
+    /// index of the connected component the unit belongs to
+    component: usize,
+    /// factor converting a value in the component root to this unit
+    from_root: ConversionFactor,
+    /// the declared dimension of the unit
+    dimension: Option<Dimension>,
+    /// whether SI and binary prefixes can be applied to the unit
+    prefixable: bool,
+    binary_prefixable: bool,
+    /// whether the unit is hidden from the user, and so never suggested
+    intermediate: bool,
+}
+
+impl CompiledUnit {
+    fn accepts_prefix(&self, kind: PrefixKind) -> bool {
+        match kind {
+            PrefixKind::Si => self.prefixable,
+            PrefixKind::Binary => self.binary_prefixable,
+        }
+    }
+}
+
+/// `CompiledGraph` struct to represent a precomputed `ConversionGraph`.
+/// Every unit stores the affine factor from the root of its connected component (the
+/// unit whose name sorts first), following the same paths `ConversionGraph::convert`
+/// would choose from that root. A conversion is then two lookups and one composition,
+/// with no graph traversal or allocation.
+///
+/// The compiled graph is immutable, so it can be shared across threads (e.g. in an `Arc`)
+/// without locking. It does not follow later changes to the graph it was built from.
+///
+/// If the graph has cycles whose factors disagree, results may differ slightly from
+/// `ConversionGraph::convert`, since conversions always go through the component root.
+#[derive(Debug, Clone)]
+pub struct CompiledGraph {
+    /// a map of aliases to unit names
+    aliases_to_name: HashMap<String, String>,
+    /// a map of unit names to their component and factor from the component root
+    units: HashMap<String, CompiledUnit>,
+    /// how aliases that match no unit exactly are looked up, as in the graph
+    unit_lookup: UnitLookup,
+    /// the aliases as lenient lookups compare them
+    alias_table: AliasTable,
+}
+
+impl CompiledGraph {
+    pub(crate) fn from_graph(graph: &ConversionGraph) -> Self {
+        let mut units: HashMap<String, CompiledUnit> = HashMap::with_capacity(graph.name_to_units.len());
+        for (component, tree) in graph.spanning_forest().into_iter().enumerate() {
+            let from_root = tree.factors_from_root(graph);
+            for unit in tree.order {
+                let declared: &Unit = &graph.name_to_units[unit];
+                units.insert(unit.to_string(), CompiledUnit {
+                    component,
+                    from_root: from_root[unit],
+                    dimension: declared.dimension(),
+                    prefixable: declared.accepts_prefix(PrefixKind::Si),
+                    binary_prefixable: declared.accepts_prefix(PrefixKind::Binary),
+                    intermediate: declared.intermediate(),
+                });
+            }
+        }
+
+        Self {
+            aliases_to_name: graph.aliases_to_name.clone(),
+            units,
+            unit_lookup: graph.unit_lookup,
+            alias_table: graph.alias_table().clone(),
+        }
+    }
+
+    /// Whether the name is an alias of a unit, or a prefix applied to a prefixable unit.
+    pub fn contains_unit(&self, name: &str) -> bool {
+        self.lookup(name).is_ok()
+    }
+
+    /// Get the composed conversion factor from one unit to another.
+    ///
+    /// # Error
+    ///
+    /// Error if either of the units do not exist in the graph or they are not connected.
+    pub fn factor_between(&self, from: &str, to: &str) -> Result<ConversionFactor, ConversionError> {
+        let (from_name, from_unit, from_prefix) = self.lookup(from)?;
+        let (to_name, to_unit, to_prefix) = self.lookup(to)?;
+        if let (Some(from_dimension), Some(to_dimension)) = (from_unit.dimension, to_unit.dimension) {
+            if from_dimension != to_dimension {
+                return Err(ConversionError::DimensionMismatch {
+                    from: from_name.to_string(),
+                    from_dimension,
+                    to: to_name.to_string(),
+                    to_dimension,
+                });
+            }
+        }
+        if from_unit.component != to_unit.component {
+            return Err(ConversionError::ConversionPathNotFound(from.to_string(), to.to_string()));
+        }
+        Ok(from_prefix
+            .then(&from_unit.from_root.inverse())
+            .then(&to_unit.from_root)
+            .then(&to_prefix.inverse()))
+    }
+
+    /// Convert a value from one unit to another.
+    ///
+    /// # Error
+    ///
+    /// Error if either of the units do not exist in the graph or they are not connected.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use conversion_wiz::ConversionGraph;
+    ///
+    /// let mut graph = ConversionGraph::new();
+    /// graph.add_unit("Celsius", vec!["C"], false).unwrap();
+    /// graph.add_unit("Fahrenheit", vec!["F"], false).unwrap();
+    /// graph.add_edge("C", "F", 1.8, 32.0).unwrap();
+    /// let compiled = graph.compile();
+    /// assert_eq!(compiled.convert("C", "F", 100.0).unwrap(), 212.0);
+    /// ```
+    pub fn convert(&self, from: &str, to: &str, value: f64) -> Result<f64, ConversionError> {
+        let factor = self.factor_between(from, to)?;
+        Ok(factor.apply(value))
+    }
+
+    /// Aliases of visible units that look like `alias`, closest first, as
+    /// `ConversionGraph::suggestions` gives them.
+    pub fn suggestions(&self, alias: &str) -> Vec<String> {
+        let visible = |name: &str| self.units.get(name).is_some_and(|unit| !unit.intermediate);
+        let accepts = |name: &str, kind: PrefixKind| self.units.get(name).is_some_and(|unit| unit.accepts_prefix(kind));
+        suggestions(alias, &self.alias_table, visible, accepts)
+    }
+
+    /// `error` with `suggestions` filled in if it is a `ConversionError::UnitNotFound`,
+    /// as `ConversionGraph::with_suggestions` does.
+    pub fn with_suggestions(&self, error: ConversionError) -> ConversionError {
+        match error {
+            ConversionError::UnitNotFound { unit, suggestions } if suggestions.is_empty() => {
+                let suggestions = self.suggestions(&unit);
+                ConversionError::UnitNotFound { unit, suggestions }
+            }
+            error => error,
+        }
+    }
+
+    /// Find the unit of an alias, with the factor from the (possibly prefixed) alias to the unit.
+    fn lookup(&self, alias: &str) -> Result<(&str, &CompiledUnit, ConversionFactor), ConversionError> {
+        let exact = |alias: &str| self.aliases_to_name.get(alias)
+            .and_then(|name| self.units.get(name).map(|unit| (name.as_str(), unit)));
+        if let Some((name, unit)) = exact(alias) {
+            return Ok((name, unit, ConversionFactor::identity()));
+        }
+        let accepts = |rest: &str, kind: PrefixKind| exact(rest).is_some_and(|(_, unit)| unit.accepts_prefix(kind));
+        let prefixed = split_prefix(alias, accepts)
+            .and_then(|(prefix, rest)| exact(rest).map(|(name, unit)| (name, unit, ConversionFactor::new(prefix.factor(), 0.0))));
+        if let Some(found) = prefixed {
+            return Ok(found);
+        }
+        let accepts = |name: &str, kind: PrefixKind| self.units.get(name).is_some_and(|unit| unit.accepts_prefix(kind));
+        match self.unit_lookup.matches(alias, &self.alias_table, accepts)[..] {
+            [(name, prefix, _)] if self.units.contains_key(name) => {
+                let factor = prefix.map_or(ConversionFactor::identity(), |prefix| ConversionFactor::new(prefix.factor(), 0.0));
+                Ok((name, &self.units[name], factor))
+            }
+            _ => Err(ConversionError::unit_not_found(alias)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+    use std::sync::Arc;
+    use std::thread;
+
+    const REL_TOL: f64 = 1e-9;
+
+    fn temperature_graph() -> ConversionGraph {
+        let mut graph = ConversionGraph::new();
+        let _ = graph.add_unit("Kelvin", vec!["K"], false);
+        let _ = graph.add_unit("Celsius", vec!["C"], false);
+        let _ = graph.add_unit("Fahrenheit", vec!["F"], false);
+        let _ = graph.add_unit("meter", vec!["m"], false);
+        let _ = graph.add_unit("kilometer", vec!["km"], false);
+        let _ = graph.add_edge("C", "K", 1.0, 273.15);
+        let _ = graph.add_edge("K", "F", 1.8, -459.67);
+        let _ = graph.add_edge("m", "km", 0.001, 0.0);
+        graph
+    }
+
+    #[test]
+    fn test_compiled_graph_matches_graph() {
+        let graph = temperature_graph();
+        let compiled = graph.compile();
+        for (from, to) in [("C", "F"), ("F", "C"), ("K", "C"), ("F", "K"), ("m", "km"), ("km", "m"), ("C", "C")] {
+            let expected = graph.convert(from, to, 37.5).unwrap();
+            assert_relative_eq!(compiled.convert(from, to, 37.5).unwrap(), expected, max_relative = REL_TOL);
+        }
+    }
+
+    #[test]
+    fn test_compiled_graph_errors() {
+        let compiled = temperature_graph().compile();
+        assert!(matches!(compiled.convert("C", "m", 1.0), Err(ConversionError::ConversionPathNotFound(_, _))));
+        assert!(matches!(compiled.convert("C", "X", 1.0), Err(ConversionError::UnitNotFound { .. })));
+        assert!(compiled.contains_unit("km"));
+    }
+
+    #[test]
+    fn test_compiled_graph_prefixes() {
+        let mut graph = ConversionGraph::new();
+        let _ = graph.insert_unit(Unit::new("meter", vec!["m"], false).unwrap().with_prefixes(PrefixKind::Si));
+        let _ = graph.add_unit("foot", vec!["ft"], false);
+        let _ = graph.add_edge("ft", "m", 0.3048, 0.0);
+        let compiled = graph.compile();
+        assert_relative_eq!(compiled.convert("km", "ft", 1.0).unwrap(), graph.convert("km", "ft", 1.0).unwrap(), max_relative = REL_TOL);
+
+        // the lookup settings of the graph carry over
+        graph.set_unit_lookup(UnitLookup::new().with_case_folding(true));
+        let compiled = graph.compile();
+        assert_relative_eq!(compiled.convert("KM", "FT", 1.0).unwrap(), 1000.0 / 0.3048, max_relative = REL_TOL);
+        let error = compiled.with_suggestions(compiled.convert("metr", "ft", 1.0).unwrap_err());
+        assert!(matches!(&error, ConversionError::UnitNotFound { suggestions, .. } if suggestions == &["meter"]), "{:?}", error);
+        assert_relative_eq!(compiled.convert("cm", "mm", 1.0).unwrap(), 10.0, max_relative = REL_TOL);
+        assert!(!compiled.contains_unit("kft"));
+    }
+
+    #[test]
+    fn test_compiled_graph_shared_across_threads() {
+        let compiled = Arc::new(temperature_graph().compile());
+        let handles: Vec<_> = (0..4)
+            .map(|i| {
+                let compiled = Arc::clone(&compiled);
+                thread::spawn(move || compiled.convert("C", "F", i as f64 * 10.0).unwrap())
+            })
+            .collect();
+        for (i, handle) in handles.into_iter().enumerate() {
+            assert_relative_eq!(handle.join().unwrap(), i as f64 * 18.0 + 32.0, max_relative = REL_TOL);
+        }
+    }
+}
@@ -0,0 +1,416 @@
+//! Compound units built from products, quotients and integer powers of registered units.
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::{ConversionError, ConversionFactor, ConversionGraph, Dimension};
+
+/// `UnitExpr` struct to represent a compound unit such as `m/s`, `kg*m/s^2` or `J/(kg*K)`.
+/// It is a list of unit aliases with their integer powers; the aliases are resolved
+/// against a `ConversionGraph` only when converting.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnitExpr {
+    factors: Vec<(String, i32)>,
+}
+
+impl UnitExpr {
+    /// An expression made of a single unit to the first power.
+    pub fn atom(alias: &str) -> Self {
+        Self {
+            factors: vec![(alias.to_string(), 1)],
+        }
+    }
+
+    /// The unit aliases and their powers. Repeated units are merged and units
+    /// whose powers cancel out are dropped, so `m*m/s/m` has factors `[("m", 1), ("s", -1)]`.
+    pub fn factors(&self) -> &[(String, i32)] {
+        &self.factors
+    }
+
+    /// The single unit this expression consists of, if it is `alias^1`.
+    pub fn as_atom(&self) -> Option<&str> {
+        match self.factors.as_slice() {
+            [(alias, 1)] => Some(alias),
+            _ => None,
+        }
+    }
+
+    /// Multiply by `alias^power`, or `None` if the power overflows.
+    fn push(&mut self, alias: &str, power: i32) -> Option<()> {
+        match self.factors.iter_mut().find(|(a, _)| a == alias) {
+            Some((_, p)) => *p = p.checked_add(power)?,
+            None => self.factors.push((alias.to_string(), power)),
+        }
+        self.factors.retain(|(_, p)| *p != 0);
+        Some(())
+    }
+
+    /// Multiply by `other^sign`, or `None` if a power overflows.
+    fn combine(&mut self, other: UnitExpr, sign: i32) -> Option<()> {
+        for (alias, power) in other.factors {
+            self.push(&alias, power.checked_mul(sign)?)?;
+        }
+        Some(())
+    }
+}
+
+/// Parses expressions such as `m/s`, `kg·m/s²`, `kg*m/s^2`, `m^-1` and `J/(kg*K)`.
+/// Operators apply left to right, so `kg/m/s^2` is `kg/(m*s^2)`; `1` stands for no unit.
+impl FromStr for UnitExpr {
+    type Err = ConversionError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = ExprParser { chars: s.chars().collect(), pos: 0 };
+        let expr = parser.expr().ok_or_else(|| ConversionError::InvalidUnitExpression(s.to_string()))?;
+        if parser.pos != parser.chars.len() {
+            return Err(ConversionError::InvalidUnitExpression(s.to_string()));
+        }
+        Ok(expr)
+    }
+}
+
+impl fmt::Display for UnitExpr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let term = |alias: &str, power: i32| match power.unsigned_abs() {
+            1 => alias.to_string(),
+            p => format!("{}^{}", alias, p),
+        };
+        let numerator: Vec<String> = self.factors.iter()
+            .filter(|(_, p)| *p > 0)
+            .map(|(a, p)| term(a, *p))
+            .collect();
+        if numerator.is_empty() {
+            write!(f, "1")?;
+        } else {
+            write!(f, "{}", numerator.join("*"))?;
+        }
+        for (alias, power) in self.factors.iter().filter(|(_, p)| *p < 0) {
+            write!(f, "/{}", term(alias, *power))?;
+        }
+        Ok(())
+    }
+}
+
+const OPERATORS: [char; 4] = ['*', '·', '⋅', '/'];
+const SUPERSCRIPTS: [char; 10] = ['⁰', '¹', '²', '³', '⁴', '⁵', '⁶', '⁷', '⁸', '⁹'];
+
+/// Recursive descent parser for `UnitExpr`.
+/// expr := term (op term)*, term := ( '(' expr ')' | name ) power?
+struct ExprParser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl ExprParser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn skip_spaces(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.pos += 1;
+        }
+    }
+
+    fn expr(&mut self) -> Option<UnitExpr> {
+        let mut expr = UnitExpr { factors: Vec::new() };
+        expr.combine(self.term()?, 1)?;
+        loop {
+            self.skip_spaces();
+            match self.peek() {
+                Some(op) if OPERATORS.contains(&op) => {
+                    self.pos += 1;
+                    let sign = if op == '/' { -1 } else { 1 };
+                    expr.combine(self.term()?, sign)?;
+                }
+                _ => return Some(expr),
+            }
+        }
+    }
+
+    fn term(&mut self) -> Option<UnitExpr> {
+        self.skip_spaces();
+        let base = if self.peek() == Some('(') {
+            self.pos += 1;
+            let inner = self.expr()?;
+            self.skip_spaces();
+            if self.peek() != Some(')') {
+                return None;
+            }
+            self.pos += 1;
+            inner
+        } else {
+            let start = self.pos;
+            while self.peek().is_some_and(|c| !OPERATORS.contains(&c) && !SUPERSCRIPTS.contains(&c) && !"()^⁻".contains(c)) {
+                self.pos += 1;
+            }
+            let name: String = self.chars[start..self.pos].iter().collect();
+            match name.trim() {
+                "" => return None,
+                "1" => UnitExpr { factors: Vec::new() },
+                name => UnitExpr::atom(name),
+            }
+        };
+        let power = self.power()?;
+        let mut term = UnitExpr { factors: Vec::new() };
+        term.combine(base, power)?;
+        Some(term)
+    }
+
+    /// `^2`, `^-1`, `²`, `⁻¹` or nothing (power 1)
+    fn power(&mut self) -> Option<i32> {
+        self.skip_spaces();
+        let mut digits = String::new();
+        if self.peek() == Some('^') {
+            self.pos += 1;
+            self.skip_spaces();
+            if self.peek() == Some('-') {
+                digits.push('-');
+                self.pos += 1;
+            }
+            while let Some(c) = self.peek().filter(char::is_ascii_digit) {
+                digits.push(c);
+                self.pos += 1;
+            }
+        } else {
+            if self.peek() == Some('⁻') {
+                digits.push('-');
+                self.pos += 1;
+            }
+            while let Some(digit) = self.peek().and_then(|c| SUPERSCRIPTS.iter().position(|&s| s == c)) {
+                digits.push_str(&digit.to_string());
+                self.pos += 1;
+            }
+            if digits.is_empty() {
+                return Some(1);
+            }
+        }
+        digits.parse().ok()
+    }
+}
+
+/// Compound unit conversions.
+impl ConversionGraph {
+    /// Parse a unit expression, treating the whole string as a single unit if it is
+    /// a registered alias (so a unit named `km/h` is not split into `km` and `h`).
+    pub fn unit_expr(&self, expr: &str) -> Result<UnitExpr, ConversionError> {
+        if self.contains_unit(expr) {
+            return Ok(UnitExpr::atom(expr));
+        }
+        expr.parse()
+    }
+
+    /// Get the conversion factor between two unit expressions.
+    ///
+    /// Each registered unit in the expressions is matched with units it can be converted to,
+    /// and the powers of every group of connected units must cancel out. Inside products and
+    /// quotients only pure scale factors can be applied, so converting e.g. `K/s` to `°C/s`
+    /// fails with `ConversionError::OffsetUnitInCompound`. A conversion between two single
+    /// units is the ordinary affine conversion.
+    ///
+    /// # Error
+    ///
+    /// Error if a unit does not exist, the dimensions of the expressions differ, or the
+    /// units of the two expressions cannot be matched.
+    pub fn factor_between_exprs(&self, from: &UnitExpr, to: &UnitExpr) -> Result<ConversionFactor, ConversionError> {
+        if let (Some(from), Some(to)) = (from.as_atom(), to.as_atom()) {
+            return self.factor_between(from, to);
+        }
+
+        // fail fast when both sides have fully known dimensions
+        if let (Some(from_dimension), Some(to_dimension)) = (self.expr_dimension(from)?, self.expr_dimension(to)?) {
+            if from_dimension != to_dimension {
+                return Err(ConversionError::DimensionMismatch {
+                    from: from.to_string(),
+                    from_dimension,
+                    to: to.to_string(),
+                    to_dimension,
+                });
+            }
+        }
+
+        // each group is a representative unit and the net power of its connected units
+        let mut groups: Vec<(&str, i32)> = Vec::new();
+        let mut scale = 1.0;
+        let signed = from.factors.iter().map(|(a, p)| (a, *p))
+            .chain(to.factors.iter().map(|(a, p)| (a, -*p)));
+        for (alias, power) in signed {
+            let mut matched = false;
+            for (representative, net_power) in groups.iter_mut() {
+                let factor = match self.factor_between(alias, representative) {
+                    Ok(factor) => factor,
+                    Err(ConversionError::ConversionPathNotFound(_, _)) | Err(ConversionError::DimensionMismatch { .. }) => continue,
+                    Err(e) => return Err(e),
+                };
+                if factor.offset() != 0.0 {
+                    return Err(ConversionError::OffsetUnitInCompound(alias.to_string()));
+                }
+                scale *= factor.scale().powi(power);
+                *net_power += power;
+                matched = true;
+                break;
+            }
+            if !matched {
+                if !self.contains_unit(alias) {
+                    return Err(ConversionError::unit_not_found(alias));
+                }
+                groups.push((alias, power));
+            }
+        }
+
+        if groups.iter().any(|(_, net_power)| *net_power != 0) {
+            return Err(ConversionError::ConversionPathNotFound(from.to_string(), to.to_string()));
+        }
+        Ok(ConversionFactor::new(scale, 0.0))
+    }
+
+    /// Convert a value between two unit expressions such as `km/h` and `m/s`.
+    /// See `factor_between_exprs`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use conversion_wiz::ConversionGraph;
+    ///
+    /// let mut graph = ConversionGraph::new();
+    /// for (name, alias) in [("meter", "m"), ("kilometer", "km"), ("second", "s"), ("hour", "h")] {
+    ///     graph.add_unit(name, vec![alias], false).unwrap();
+    /// }
+    /// graph.add_edge("km", "m", 1000.0, 0.0).unwrap();
+    /// graph.add_edge("h", "s", 3600.0, 0.0).unwrap();
+    /// let speed = graph.convert_compound("km/h", "m/s", 36.0).unwrap();
+    /// assert!((speed - 10.0).abs() < 1e-9);
+    /// ```
+    pub fn convert_compound(&self, from: &str, to: &str, value: f64) -> Result<f64, ConversionError> {
+        let factor = self.factor_between_exprs(&self.unit_expr(from)?, &self.unit_expr(to)?)?;
+        Ok(factor.apply(value))
+    }
+
+    /// The dimension of an expression, if every unit in it declares one. An expression
+    /// whose exponents overflow is an invalid dimension.
+    fn expr_dimension(&self, expr: &UnitExpr) -> Result<Option<Dimension>, ConversionError> {
+        let overflow = || ConversionError::InvalidDimension(expr.to_string());
+        let mut dimension = Dimension::dimensionless();
+        for (alias, power) in &expr.factors {
+            let name = self.resolve(alias)?.name;
+            let Some(unit_dimension) = self.name_to_units[name].dimension() else {
+                return Ok(None);
+            };
+            let power = i8::try_from(*power).map_err(|_| overflow())?;
+            dimension = unit_dimension.checked_powi(power)
+                .and_then(|d| dimension.checked_mul(d))
+                .ok_or_else(overflow)?;
+        }
+        Ok(Some(dimension))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Unit;
+    use approx::assert_relative_eq;
+
+    const REL_TOL: f64 = 1e-9;
+
+    fn si_graph() -> ConversionGraph {
+        let mut graph = ConversionGraph::new();
+        let units = [
+            ("meter", "m", "length"),
+            ("kilometer", "km", "length"),
+            ("second", "s", "time"),
+            ("hour", "h", "time"),
+            ("kilogram", "kg", "mass"),
+            ("gram", "g", "mass"),
+            ("Kelvin", "K", "temperature"),
+            ("Celsius", "C", "temperature"),
+        ];
+        for (name, alias, dimension) in units {
+            let unit = Unit::new(name, vec![alias], false).unwrap().with_dimension(dimension.parse().unwrap());
+            let _ = graph.insert_unit(unit);
+        }
+        let _ = graph.add_edge("km", "m", 1000.0, 0.0);
+        let _ = graph.add_edge("h", "s", 3600.0, 0.0);
+        let _ = graph.add_edge("kg", "g", 1000.0, 0.0);
+        let _ = graph.add_edge("C", "K", 1.0, 273.15);
+        graph
+    }
+
+    #[test]
+    fn test_unit_expr_parse() {
+        let expr: UnitExpr = "kg·m/s²".parse().unwrap();
+        assert_eq!(expr.factors(), &[("kg".to_string(), 1), ("m".to_string(), 1), ("s".to_string(), -2)]);
+        assert_eq!("kg*m/s^2".parse::<UnitExpr>().unwrap(), expr);
+        assert_eq!("kg * m * s^-2".parse::<UnitExpr>().unwrap(), expr);
+        assert_eq!("J/(kg*K)".parse::<UnitExpr>().unwrap().to_string(), "J/kg/K");
+        assert_eq!("m*m/s/m".parse::<UnitExpr>().unwrap().to_string(), "m/s");
+        assert_eq!("1/s".parse::<UnitExpr>().unwrap().to_string(), "1/s");
+        assert_eq!("m⁻¹".parse::<UnitExpr>().unwrap().to_string(), "1/m");
+    }
+
+    #[test]
+    fn test_unit_expr_parse_invalid() {
+        for expr in ["", "m/", "(m/s", "m^", "m^x", "*m", "m)"] {
+            assert!(matches!(expr.parse::<UnitExpr>(), Err(ConversionError::InvalidUnitExpression(_))), "{}", expr);
+        }
+    }
+
+    #[test]
+    fn test_unit_expr_power_overflow() {
+        for expr in ["(m^100000)^100000", "1/m^-2147483648", "m^2147483647*m"] {
+            assert!(matches!(expr.parse::<UnitExpr>(), Err(ConversionError::InvalidUnitExpression(e)) if e == expr), "{}", expr);
+        }
+        let expr: UnitExpr = "m^-2147483648".parse().unwrap();
+        assert_eq!(expr.factors(), &[("m".to_string(), i32::MIN)]);
+        assert_eq!(expr.to_string(), "1/m^2147483648");
+    }
+
+    #[test]
+    fn test_convert_compound_speed() {
+        let graph = si_graph();
+        assert_relative_eq!(graph.convert_compound("km/h", "m/s", 36.0).unwrap(), 10.0, max_relative = REL_TOL);
+        assert_relative_eq!(graph.convert_compound("m/s", "km/h", 10.0).unwrap(), 36.0, max_relative = REL_TOL);
+    }
+
+    #[test]
+    fn test_convert_compound_force() {
+        let graph = si_graph();
+        let value = graph.convert_compound("kg·m/s²", "g*km/h^2", 1.0).unwrap();
+        // 1 kg m/s^2 = 1000 g * 0.001 km / (1/3600 h)^2
+        assert_relative_eq!(value, 3600.0 * 3600.0, max_relative = REL_TOL);
+    }
+
+    #[test]
+    fn test_convert_compound_errors() {
+        let graph = si_graph();
+        assert!(matches!(graph.convert_compound("m/s", "kg", 1.0), Err(ConversionError::DimensionMismatch { .. })));
+        assert!(matches!(graph.convert_compound("K/s", "C/s", 1.0), Err(ConversionError::OffsetUnitInCompound(_))));
+        assert!(matches!(graph.convert_compound("m/x", "m/s", 1.0), Err(ConversionError::UnitNotFound { .. })));
+        // scale-only conversions of a temperature unit stay allowed
+        assert_relative_eq!(graph.convert_compound("K/s", "K/h", 1.0).unwrap(), 3600.0, max_relative = REL_TOL);
+        // single units keep their affine conversion
+        assert_relative_eq!(graph.convert_compound("C", "K", 0.0).unwrap(), 273.15, max_relative = REL_TOL);
+        // exponents that do not fit a dimension
+        for (from, to) in [("m^200", "km^200"), ("m^100*km^100", "m^2"), ("m^127*m", "m")] {
+            assert!(matches!(graph.convert_compound(from, to, 1.0), Err(ConversionError::InvalidDimension(_))), "{} -> {}", from, to);
+        }
+    }
+
+    #[test]
+    fn test_convert_compound_registered_name_wins() {
+        let mut graph = si_graph();
+        let _ = graph.add_unit("knot", vec!["kn"], false);
+        let _ = graph.add_unit("kilometer per hour", vec!["km/h"], false);
+        let _ = graph.add_edge("kn", "km/h", 1.852, 0.0);
+        assert_relative_eq!(graph.convert_compound("kn", "km/h", 10.0).unwrap(), 18.52, max_relative = REL_TOL);
+    }
+
+    #[test]
+    fn test_convert_compound_prefixed_units() {
+        let mut graph = ConversionGraph::new();
+        let _ = graph.insert_unit(Unit::new("meter", vec!["m"], false).unwrap().with_prefixes(crate::PrefixKind::Si));
+        let _ = graph.insert_unit(Unit::new("second", vec!["s"], false).unwrap().with_prefixes(crate::PrefixKind::Si));
+        assert_relative_eq!(graph.convert_compound("km/ms", "m/s", 1.0).unwrap(), 1e6, max_relative = REL_TOL);
+        assert_relative_eq!(graph.convert_compound("mm^2", "m^2", 1.0).unwrap(), 1e-6, max_relative = REL_TOL);
+    }
+}
@@ -0,0 +1,679 @@
+//! Config file model, as read from `data.json` (or its TOML and YAML equivalents).
+
+use std::error::Error;
+use std::fmt;
+use std::path::Path;
+
+use num_rational::BigRational;
+use serde::de::{self, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::rational::{rational_from_f64, rational_to_f64, rational_to_string};
+use crate::{parse_rational, ConversionError, ConversionFactor, ConversionGraph, Dimension, Precision, PrefixKind, RationalFactor, Unit, UnitLookup};
+
+/// `UnitConfig` struct to represent a unit entry of the config.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UnitConfig {
+    pub name: String,
+    pub aliases: Vec<String>,
+    pub intermediate: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dimension: Option<String>,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub prefixable: bool,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub binary_prefixable: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub precision: Option<Precision>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub category: Option<String>,
+    /// replace a unit of the same name from an earlier file of a catalog (see `CatalogLoader`)
+    #[serde(default, rename = "override", skip_serializing_if = "is_false")]
+    pub overrides: bool,
+}
+
+impl UnitConfig {
+    /// Build the unit described by the entry.
+    pub fn to_unit(&self) -> Result<Unit, ConversionError> {
+        let mut unit = Unit::new(&self.name, self.aliases.iter().map(AsRef::as_ref).collect(), self.intermediate)?;
+        if let Some(dimension) = &self.dimension {
+            let dimension: Dimension = dimension.parse()?;
+            unit = unit.with_dimension(dimension);
+        }
+        if self.prefixable {
+            unit = unit.with_prefixes(PrefixKind::Si);
+        }
+        if self.binary_prefixable {
+            unit = unit.with_prefixes(PrefixKind::Binary);
+        }
+        if let Some(precision) = self.precision {
+            unit = unit.with_precision(precision);
+        }
+        if let Some(category) = &self.category {
+            unit = unit.with_category(category);
+        }
+        Ok(unit)
+    }
+
+    /// The entry describing a unit.
+    pub fn from_unit(unit: &Unit) -> Self {
+        Self {
+            name: unit.name().to_string(),
+            aliases: unit.aliases().to_vec(),
+            intermediate: unit.intermediate(),
+            dimension: unit.dimension().map(|d| d.to_string()),
+            prefixable: unit.accepts_prefix(PrefixKind::Si),
+            binary_prefixable: unit.accepts_prefix(PrefixKind::Binary),
+            precision: unit.precision(),
+            category: unit.category().map(str::to_string),
+            overrides: false,
+        }
+    }
+}
+
+/// `ConfigNumber` struct to represent a factor or offset of the config. It is written as
+/// a number, or as a string holding a decimal or a fraction (`"1200/3937"`) to give the
+/// exact value used in rational mode (see `ConversionGraph::set_rational_mode`).
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigNumber {
+    value: f64,
+    /// the exact value, when written as a string
+    rational: Option<BigRational>,
+}
+
+impl ConfigNumber {
+    /// A number with an exact value. It is written as a plain number when the float
+    /// reads back as the same value, and as a string otherwise.
+    pub fn from_rational(rational: BigRational) -> Self {
+        let value = rational_to_f64(&rational);
+        let exact = value.is_finite() && rational_from_f64(value) == rational;
+        Self { value, rational: (!exact).then_some(rational) }
+    }
+
+    /// The value as a float.
+    pub fn value(&self) -> f64 {
+        self.value
+    }
+
+    /// The exact value: the string it was written as, or else the shortest decimal that
+    /// rounds to the float. `None` for infinite and NaN values.
+    pub fn rational(&self) -> Option<BigRational> {
+        match &self.rational {
+            Some(rational) => Some(rational.clone()),
+            None => self.value.is_finite().then(|| rational_from_f64(self.value)),
+        }
+    }
+}
+
+impl From<f64> for ConfigNumber {
+    fn from(value: f64) -> Self {
+        Self { value, rational: None }
+    }
+}
+
+impl Serialize for ConfigNumber {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match &self.rational {
+            Some(rational) => serializer.serialize_str(&rational_to_string(rational)),
+            None => serializer.serialize_f64(self.value),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for ConfigNumber {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct NumberVisitor;
+
+        impl Visitor<'_> for NumberVisitor {
+            type Value = ConfigNumber;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, "a number, or a string holding a decimal or a fraction")
+            }
+
+            fn visit_f64<E: de::Error>(self, value: f64) -> Result<ConfigNumber, E> {
+                Ok(value.into())
+            }
+
+            fn visit_i64<E: de::Error>(self, value: i64) -> Result<ConfigNumber, E> {
+                Ok((value as f64).into())
+            }
+
+            fn visit_u64<E: de::Error>(self, value: u64) -> Result<ConfigNumber, E> {
+                Ok((value as f64).into())
+            }
+
+            fn visit_str<E: de::Error>(self, text: &str) -> Result<ConfigNumber, E> {
+                let rational = parse_rational(text).ok_or_else(|| E::custom(format!("invalid number '{}'", text)))?;
+                Ok(ConfigNumber { value: rational_to_f64(&rational), rational: Some(rational) })
+            }
+        }
+
+        deserializer.deserialize_any(NumberVisitor)
+    }
+}
+
+/// A scale-only conversion `to = factor * from`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ConversionScale {
+    pub from: String,
+    pub to: String,
+    pub factor: ConfigNumber,
+}
+
+/// An offset-only conversion `to = from + offset`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ConversionOffset {
+    pub from: String,
+    pub to: String,
+    pub offset: ConfigNumber,
+}
+
+/// A general affine conversion `to = factor * from + offset`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Conversion {
+    pub from: String,
+    pub to: String,
+    #[serde(default = "default_factor")]
+    pub factor: ConfigNumber,
+    #[serde(default = "default_offset")]
+    pub offset: ConfigNumber,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub exact: bool,
+}
+
+fn default_factor() -> ConfigNumber {
+    1.0.into()
+}
+
+fn default_offset() -> ConfigNumber {
+    0.0.into()
+}
+
+fn is_false(value: &bool) -> bool {
+    !value
+}
+
+/// `Config` struct to represent a whole config file: the units, then the conversions
+/// between them. Conversions are added in the order `conversions`, `conversions_scale`,
+/// `conversions_offset`.
+///
+/// `include` (also spelled `extends`) lists other config files to merge first, relative
+/// to this one. It is only followed by `CatalogLoader`; `ConversionGraph::from_config`
+/// ignores it.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Config {
+    #[serde(default, alias = "extends", skip_serializing_if = "Vec::is_empty")]
+    pub include: Vec<String>,
+    pub units: Vec<UnitConfig>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub conversions: Vec<Conversion>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub conversions_scale: Vec<ConversionScale>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub conversions_offset: Vec<ConversionOffset>,
+}
+
+/// How config entries refer to their units: by exact alias (or a prefixed alias),
+/// whatever the lookup of the graph they are loaded into. Shared by `load_config` and
+/// `ConfigValidator`, so a config that validates also loads.
+pub(crate) const CONFIG_LOOKUP: UnitLookup = UnitLookup::exact();
+
+/// `ConfigFormat` enum to represent the file formats a `Config` can be written in.
+/// All formats share the same schema.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigFormat {
+    Json,
+    Toml,
+    Yaml,
+}
+
+impl ConfigFormat {
+    /// The format matching a file extension (`json`, `toml`, `yaml` or `yml`).
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        match extension.to_ascii_lowercase().as_str() {
+            "json" => Some(ConfigFormat::Json),
+            "toml" => Some(ConfigFormat::Toml),
+            "yaml" | "yml" => Some(ConfigFormat::Yaml),
+            _ => None,
+        }
+    }
+
+    /// The format of a file, judged by its extension.
+    pub fn from_path(path: impl AsRef<Path>) -> Option<Self> {
+        path.as_ref().extension().and_then(|e| e.to_str()).and_then(Self::from_extension)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ConfigFormat::Json => "JSON",
+            ConfigFormat::Toml => "TOML",
+            ConfigFormat::Yaml => "YAML",
+        }
+    }
+}
+
+/// `ConfigError` struct to represent a config that could not be parsed or written,
+/// with the line and column of the problem when the format reports one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigError {
+    format: ConfigFormat,
+    message: String,
+    /// line and column, both starting at 1
+    location: Option<(usize, usize)>,
+}
+
+impl ConfigError {
+    fn new(format: ConfigFormat, message: impl fmt::Display, location: Option<(usize, usize)>) -> Self {
+        let message = message.to_string();
+        // some formats end their message with the location, which is reported separately
+        let message = match (location, message.rsplit_once(" at line ")) {
+            (Some(_), Some((message, _))) => message.to_string(),
+            _ => message,
+        };
+        Self { format, message, location }
+    }
+
+    pub fn format(&self) -> ConfigFormat {
+        self.format
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// Line of the problem, starting at 1.
+    pub fn line(&self) -> Option<usize> {
+        self.location.map(|(line, _)| line)
+    }
+
+    /// Column of the problem, starting at 1.
+    pub fn column(&self) -> Option<usize> {
+        self.location.map(|(_, column)| column)
+    }
+}
+
+impl Error for ConfigError {}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.location {
+            Some((line, column)) => write!(f, "Invalid {} at line {}, column {}: {}", self.format.name(), line, column, self.message),
+            None => write!(f, "Invalid {}: {}", self.format.name(), self.message),
+        }
+    }
+}
+
+/// Line and column (from 1) of a byte offset into a text.
+fn line_column(text: &str, offset: usize) -> (usize, usize) {
+    let before = &text[..offset.min(text.len())];
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    (before.matches('\n').count() + 1, before[line_start..].chars().count() + 1)
+}
+
+/// One conversion of a config, whichever list it was declared in.
+pub(crate) struct EdgeConfig<'a> {
+    /// JSON path of the entry, e.g. `$.conversions_scale[2]`
+    pub path: String,
+    pub from: &'a str,
+    pub to: &'a str,
+    pub factor: ConfigNumber,
+    pub offset: ConfigNumber,
+    pub exact: bool,
+}
+
+impl Config {
+    /// Parse a config written in the given format.
+    ///
+    /// # Error
+    ///
+    /// Error if the text is not valid in the format or does not match the config schema,
+    /// with the line and column of the problem.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use conversion_wiz::{Config, ConfigFormat};
+    ///
+    /// let toml = r#"
+    /// [[units]]
+    /// name = "meter"
+    /// aliases = ["m"]
+    /// intermediate = false
+    /// "#;
+    /// let config = Config::parse(toml, ConfigFormat::Toml).unwrap();
+    /// assert_eq!(config.units[0].name, "meter");
+    ///
+    /// let error = Config::parse("units:\n  - name: meter\n", ConfigFormat::Yaml).unwrap_err();
+    /// assert_eq!(error.line(), Some(2));
+    /// ```
+    pub fn parse(text: &str, format: ConfigFormat) -> Result<Self, ConfigError> {
+        match format {
+            ConfigFormat::Json => serde_json::from_str(text)
+                .map_err(|e| ConfigError::new(format, &e, (e.line() > 0).then(|| (e.line(), e.column())))),
+            ConfigFormat::Toml => toml::from_str(text)
+                .map_err(|e| ConfigError::new(format, e.message(), e.span().map(|span| line_column(text, span.start)))),
+            ConfigFormat::Yaml => serde_yaml::from_str(text)
+                .map_err(|e| ConfigError::new(format, &e, e.location().map(|l| (l.line(), l.column())))),
+        }
+    }
+
+    /// Write the config in the given format.
+    ///
+    /// # Error
+    ///
+    /// Error if the config cannot be represented in the format.
+    pub fn to_string(&self, format: ConfigFormat) -> Result<String, ConfigError> {
+        match format {
+            ConfigFormat::Json => serde_json::to_string_pretty(self).map_err(|e| ConfigError::new(format, e, None)),
+            ConfigFormat::Toml => toml::to_string_pretty(self).map_err(|e| ConfigError::new(format, e, None)),
+            ConfigFormat::Yaml => serde_yaml::to_string(self).map_err(|e| ConfigError::new(format, e, None)),
+        }
+    }
+
+    /// Every conversion of the config, in the order they are added to the graph.
+    pub(crate) fn edges(&self) -> Vec<EdgeConfig<'_>> {
+        let general = self.conversions.iter().enumerate().map(|(i, c)| EdgeConfig {
+            path: format!("$.conversions[{}]", i),
+            from: &c.from,
+            to: &c.to,
+            factor: c.factor.clone(),
+            offset: c.offset.clone(),
+            exact: c.exact,
+        });
+        let scale = self.conversions_scale.iter().enumerate().map(|(i, c)| EdgeConfig {
+            path: format!("$.conversions_scale[{}]", i),
+            from: &c.from,
+            to: &c.to,
+            factor: c.factor.clone(),
+            offset: 0.0.into(),
+            exact: false,
+        });
+        let offset = self.conversions_offset.iter().enumerate().map(|(i, c)| EdgeConfig {
+            path: format!("$.conversions_offset[{}]", i),
+            from: &c.from,
+            to: &c.to,
+            factor: 1.0.into(),
+            offset: c.offset.clone(),
+            exact: false,
+        });
+        general.chain(scale).chain(offset).collect()
+    }
+}
+
+/// Loading a graph from a config and saving it back.
+impl ConversionGraph {
+    /// Build a graph from a config, stopping at the first invalid entry.
+    /// Use `ConfigValidator` to get every problem of a config at once.
+    ///
+    /// # Error
+    ///
+    /// Error if a unit or a conversion of the config cannot be added to the graph.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use conversion_wiz::{Config, ConversionGraph};
+    ///
+    /// let config: Config = serde_json::from_str(r#"{
+    ///     "units": [
+    ///         { "name": "Celsius", "aliases": ["C"], "intermediate": false },
+    ///         { "name": "Fahrenheit", "aliases": ["F"], "intermediate": false }
+    ///     ],
+    ///     "conversions": [{ "from": "C", "to": "F", "factor": 1.8, "offset": 32.0 }]
+    /// }"#).unwrap();
+    /// let graph = ConversionGraph::from_config(&config).unwrap();
+    /// assert_eq!(graph.convert("C", "F", 100.0).unwrap(), 212.0);
+    /// ```
+    pub fn from_config(config: &Config) -> Result<Self, ConversionError> {
+        let mut graph = ConversionGraph::new();
+        graph.load_config(config)?;
+        Ok(graph)
+    }
+
+    /// Describe the graph as a config. Units are listed by name, and every conversion is
+    /// written once to `conversions`, in the direction it was added and with any prefixes
+    /// folded into the factor.
+    ///
+    /// Loading the result with `from_config` gives back the same units and exactly the
+    /// same factors, so conversions on it give bit-for-bit the same results. This also
+    /// holds after a trip through JSON, since floats are written in a form that parses
+    /// back to the same value. Graph settings such as the `TieBreaker` are not part of
+    /// the config.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use conversion_wiz::ConversionGraph;
+    ///
+    /// let mut graph = ConversionGraph::new();
+    /// graph.add_unit("Celsius", vec!["C"], false).unwrap();
+    /// graph.add_unit("Fahrenheit", vec!["F"], false).unwrap();
+    /// graph.add_edge("C", "F", 1.8, 32.0).unwrap();
+    ///
+    /// let json = serde_json::to_string_pretty(&graph.to_config()).unwrap();
+    /// let loaded = ConversionGraph::from_config(&serde_json::from_str(&json).unwrap()).unwrap();
+    /// assert_eq!(loaded.convert("F", "C", 451.0).unwrap(), graph.convert("F", "C", 451.0).unwrap());
+    /// ```
+    pub fn to_config(&self) -> Config {
+        let units = self.units().into_iter().map(UnitConfig::from_unit).collect();
+        let conversions = self.edges.iter()
+            .flat_map(|(from, edges)| edges.iter().map(move |(to, edge)| (from, to, edge)))
+            .filter(|(_, _, edge)| edge.declared)
+            .map(|(from, to, edge)| {
+                let (factor, offset) = match &edge.rational {
+                    Some(rational) => (
+                        ConfigNumber::from_rational(rational.scale().clone()),
+                        ConfigNumber::from_rational(rational.offset().clone()),
+                    ),
+                    None => (edge.factor.scale().into(), edge.factor.offset().into()),
+                };
+                Conversion { from: from.clone(), to: to.clone(), factor, offset, exact: edge.exact }
+            })
+            .collect();
+        Config {
+            include: Vec::new(),
+            units,
+            conversions,
+            conversions_scale: Vec::new(),
+            conversions_offset: Vec::new(),
+        }
+    }
+
+    /// Add the units and conversions of a config to the graph, using the graph's
+    /// settings such as its `DuplicateEdgePolicy`. Stops at the first invalid entry,
+    /// keeping the entries added before it.
+    ///
+    /// Conversions refer to their units by exact alias (or a prefixed alias), whatever
+    /// the graph's `UnitLookup`: a config means the same with every lookup setting.
+    ///
+    /// # Error
+    ///
+    /// Error if a unit or a conversion of the config cannot be added to the graph.
+    pub fn load_config(&mut self, config: &Config) -> Result<(), ConversionError> {
+        let lookup = std::mem::replace(&mut self.unit_lookup, CONFIG_LOOKUP);
+        let result = self.load_entries(config);
+        self.unit_lookup = lookup;
+        result
+    }
+
+    fn load_entries(&mut self, config: &Config) -> Result<(), ConversionError> {
+        for unit_config in &config.units {
+            self.insert_unit(unit_config.to_unit()?)?;
+        }
+        for edge in config.edges() {
+            self.load_edge(&edge)?;
+        }
+        Ok(())
+    }
+
+    /// Add the conversion of one config entry, with its exact factor in rational mode.
+    /// Shared by `load_config` and `ConfigValidator`, so both accept the same entries.
+    pub(crate) fn load_edge(&mut self, edge: &EdgeConfig<'_>) -> Result<(), ConversionError> {
+        let factor = ConversionFactor::new(edge.factor.value(), edge.offset.value());
+        let rational = match (self.rational_mode, edge.factor.rational(), edge.offset.rational()) {
+            (true, Some(scale), Some(offset)) => Some(RationalFactor::new(scale, offset)),
+            _ => None,
+        };
+        self.insert_factor_edge(edge.from, edge.to, factor, rational, edge.exact)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::NumberFormat;
+
+    #[test]
+    fn test_config_edges_in_load_order() {
+        let config: Config = serde_json::from_str(r#"{
+            "units": [],
+            "conversions_offset": [{ "from": "a", "to": "b", "offset": 2.0 }],
+            "conversions_scale": [{ "from": "b", "to": "c", "factor": 3.0 }],
+            "conversions": [{ "from": "c", "to": "d", "exact": true }]
+        }"#).unwrap();
+        let edges: Vec<(String, f64, f64, bool)> = config.edges().into_iter()
+            .map(|e| (e.path, e.factor.value(), e.offset.value(), e.exact))
+            .collect();
+        assert_eq!(edges, vec![
+            ("$.conversions[0]".to_string(), 1.0, 0.0, true),
+            ("$.conversions_scale[0]".to_string(), 3.0, 0.0, false),
+            ("$.conversions_offset[0]".to_string(), 1.0, 2.0, false),
+        ]);
+    }
+
+    #[test]
+    fn test_from_config_stops_at_first_error() {
+        let config: Config = serde_json::from_str(r#"{
+            "units": [{ "name": "meter", "aliases": ["m"], "intermediate": false, "prefixable": true }],
+            "conversions_scale": [{ "from": "m", "to": "ft", "factor": 3.28084 }]
+        }"#).unwrap();
+        assert!(matches!(ConversionGraph::from_config(&config), Err(ConversionError::UnitNotFound { unit, .. }) if unit == "ft"));
+    }
+
+    /// Every unit and directed edge of a graph, for comparing two graphs exactly.
+    fn snapshot(graph: &ConversionGraph) -> Vec<(String, String, u64, u64, bool)> {
+        let mut edges: Vec<_> = graph.edges.iter()
+            .flat_map(|(from, edges)| edges.iter().map(move |(to, edge)| {
+                (from.clone(), to.clone(), edge.factor.scale().to_bits(), edge.factor.offset().to_bits(), edge.exact)
+            }))
+            .collect();
+        edges.sort();
+        edges
+    }
+
+    #[test]
+    fn test_to_config_round_trip() {
+        let data = std::fs::read_to_string(concat!(env!("CARGO_MANIFEST_DIR"), "/data.json")).unwrap();
+        let graph = ConversionGraph::from_config(&serde_json::from_str(&data).unwrap()).unwrap();
+
+        let json = serde_json::to_string_pretty(&graph.to_config()).unwrap();
+        let loaded = ConversionGraph::from_config(&serde_json::from_str(&json).unwrap()).unwrap();
+        assert_eq!(snapshot(&loaded), snapshot(&graph));
+        assert_eq!(loaded.to_config(), graph.to_config());
+        assert_eq!(loaded.convert("C", "F", 37.0).unwrap().to_bits(), graph.convert("C", "F", 37.0).unwrap().to_bits());
+    }
+
+    #[test]
+    fn test_to_config_keeps_declared_direction_and_settings() {
+        let mut graph = ConversionGraph::new();
+        let meter = Unit::new("meter", vec!["m"], false).unwrap()
+            .with_dimension("length".parse().unwrap())
+            .with_category("length")
+            .with_prefixes(PrefixKind::Si);
+        let _ = graph.insert_unit(meter);
+        let _ = graph.add_unit("foot", vec!["ft"], false);
+        let _ = graph.add_exact_edge("ft", "km", 0.0003048, 0.0);
+
+        let config = graph.to_config();
+        assert_eq!(config.units[1].dimension.as_deref(), Some("length"));
+        assert_eq!(config.units[1].category.as_deref(), Some("length"));
+        assert!(config.units[1].prefixable && !config.units[1].binary_prefixable);
+        assert_eq!(config.conversions.len(), 1);
+        let conversion = &config.conversions[0];
+        assert_eq!((conversion.from.as_str(), conversion.to.as_str(), conversion.exact), ("foot", "meter", true));
+        assert_eq!(conversion.factor.value(), graph.edges["foot"]["meter"].factor.scale());
+        assert_eq!(snapshot(&ConversionGraph::from_config(&config).unwrap()), snapshot(&graph));
+    }
+
+    #[test]
+    fn test_config_formats_agree() {
+        let json = std::fs::read_to_string(concat!(env!("CARGO_MANIFEST_DIR"), "/data.json")).unwrap();
+        let config = Config::parse(&json, ConfigFormat::Json).unwrap();
+        for format in [ConfigFormat::Toml, ConfigFormat::Yaml] {
+            let text = config.to_string(format).unwrap();
+            assert_eq!(Config::parse(&text, format).unwrap(), config, "{}", format.name());
+        }
+    }
+
+    #[test]
+    fn test_config_integer_factors() {
+        let toml = "units = []\n[[conversions_scale]]\nfrom = \"a\"\nto = \"b\"\nfactor = 1000\n";
+        assert_eq!(Config::parse(toml, ConfigFormat::Toml).unwrap().conversions_scale[0].factor.value(), 1000.0);
+        let yaml = "units: []\nconversions_scale:\n  - { from: a, to: b, factor: 1000 }\n";
+        assert_eq!(Config::parse(yaml, ConfigFormat::Yaml).unwrap().conversions_scale[0].factor.value(), 1000.0);
+    }
+
+    #[test]
+    fn test_config_parse_error_location() {
+        let json = "{\n  \"units\": [\n    { \"name\": 3 }\n  ]\n}";
+        let error = Config::parse(json, ConfigFormat::Json).unwrap_err();
+        assert_eq!((error.line(), error.format()), (Some(3), ConfigFormat::Json));
+        assert!(!error.message().contains("at line"));
+
+        let toml = "units = []\n\n[[conversions]]\nfrom = \"a\"\nto = \"b\"\nfactor = \"x\"\n";
+        let error = Config::parse(toml, ConfigFormat::Toml).unwrap_err();
+        assert_eq!((error.line(), error.column()), (Some(6), Some(10)));
+        assert!(error.to_string().starts_with("Invalid TOML at line 6, column 10"));
+
+        let yaml = "units:\n  - name: meter\n    aliases: m\n    intermediate: false\n";
+        assert_eq!(Config::parse(yaml, ConfigFormat::Yaml).unwrap_err().line(), Some(3));
+    }
+
+    #[test]
+    fn test_config_format_from_path() {
+        assert_eq!(ConfigFormat::from_path("catalog/units.TOML"), Some(ConfigFormat::Toml));
+        assert_eq!(ConfigFormat::from_path("units.yml"), Some(ConfigFormat::Yaml));
+        assert_eq!(ConfigFormat::from_path("data.json"), Some(ConfigFormat::Json));
+        assert_eq!(ConfigFormat::from_path("data"), None);
+    }
+
+    #[test]
+    fn test_config_numbers_as_strings() {
+        let yaml = "units:\n  - { name: inch, aliases: [in], intermediate: false }\n  - { name: foot, aliases: [ft], intermediate: false }\n  - { name: survey foot, aliases: [ftUS], intermediate: false }\nconversions:\n  - { from: in, to: ft, factor: 1/12 }\n  - { from: ftUS, to: ft, factor: \"0.3048006096012192024384048768\" }\n";
+        let config = Config::parse(yaml, ConfigFormat::Yaml).unwrap();
+        assert_eq!(config.conversions[0].factor.value(), 1.0 / 12.0);
+        assert_eq!(config.conversions[0].factor.rational(), parse_rational("1/12"));
+
+        let mut graph = ConversionGraph::new();
+        graph.set_rational_mode(true);
+        graph.load_config(&config).unwrap();
+        assert_eq!(graph.convert_decimal("ft", "in", "1", 30).unwrap(), format!("12.{}", "0".repeat(30)));
+
+        // exact factors survive a trip through the config
+        let json = serde_json::to_string_pretty(&graph.to_config()).unwrap();
+        assert!(json.contains(r#""factor": "1/12""#), "{}", json);
+        let mut loaded = ConversionGraph::new();
+        loaded.set_rational_mode(true);
+        loaded.load_config(&serde_json::from_str(&json).unwrap()).unwrap();
+        assert_eq!(loaded.rational_factor_between("ftUS", "in").unwrap(), graph.rational_factor_between("ftUS", "in").unwrap());
+
+        let error = Config::parse(r#"{ "units": [], "conversions": [{ "from": "a", "to": "b", "factor": "1/x" }] }"#, ConfigFormat::Json).unwrap_err();
+        assert!(error.message().contains("invalid number '1/x'"), "{}", error);
+    }
+
+    #[test]
+    fn test_config_unit_precision() {
+        let toml = "[[units]]\nname = \"dollar\"\naliases = [\"USD\"]\nintermediate = false\nprecision = { decimals = 2 }\n\n[[units]]\nname = \"cent\"\naliases = [\"c\"]\nintermediate = false\nprecision = \"auto\"\n\n[[conversions]]\nfrom = \"USD\"\nto = \"c\"\nfactor = 100\n";
+        let graph = ConversionGraph::from_config(&Config::parse(toml, ConfigFormat::Toml).unwrap()).unwrap();
+        let format = NumberFormat::new();
+        assert_eq!(graph.format_value(graph.convert("c", "USD", 1234.0).unwrap(), "USD", &format), "12.34");
+        assert_eq!(graph.format_value(5.0, "dollar", &format), "5.00");
+        assert_eq!(graph.format_value(5.0, "c", &format), "5");
+        assert_eq!(graph.format_value(5.0, "USD", &format.clone().with_precision(Precision::SignificantFigures(3))), "5.00");
+
+        let json = serde_json::to_string(&graph.to_config()).unwrap();
+        assert!(json.contains(r#""precision":{"decimals":2}"#), "{}", json);
+        assert!(json.contains(r#""precision":"auto""#), "{}", json);
+    }
+}
@@ -0,0 +1,178 @@
+//! Checking that redundant conversion paths agree with each other.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use crate::{ConversionFactor, ConversionGraph};
+
+/// `InconsistentCycle` struct to represent a cycle of conversions that does not give
+/// back the value it started with, as found by `ConversionGraph::check_consistency`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InconsistentCycle {
+    /// canonical unit names around the cycle, the first unit repeated at the end
+    units: Vec<String>,
+    /// the factors of the cycle composed, starting and ending at the first unit
+    factor: ConversionFactor,
+}
+
+impl InconsistentCycle {
+    /// The units around the cycle; the first and last unit are the same.
+    pub fn units(&self) -> &[String] {
+        &self.units
+    }
+
+    /// The edges of the cycle as (from, to) pairs, in the order they are walked.
+    pub fn edges(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.units.windows(2).map(|pair| (pair[0].as_str(), pair[1].as_str()))
+    }
+
+    /// The composition of the factors around the cycle. It would be the identity if
+    /// the conversions agreed.
+    pub fn factor(&self) -> ConversionFactor {
+        self.factor
+    }
+}
+
+/// Displays the cycle and its composed factor, e.g. `kilometer -> meter -> mile -> kilometer (x * 1.00584 + 0)`.
+impl fmt::Display for InconsistentCycle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({})", self.units.join(" -> "), self.factor)
+    }
+}
+
+/// Consistency checks.
+impl ConversionGraph {
+    /// Find cycles of conversions whose composed factors deviate from the identity by
+    /// more than `tolerance` (see `ConversionFactor::approx_eq`).
+    ///
+    /// Only a cycle basis is checked: a spanning tree is built for each connected component,
+    /// and every edge outside the tree closes exactly one cycle with the tree. Any other
+    /// cycle is a combination of these, so the graph is consistent if and only if every
+    /// basis cycle is. Each reported cycle starts with the edge outside the tree.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use conversion_wiz::ConversionGraph;
+    ///
+    /// let mut graph = ConversionGraph::new();
+    /// graph.add_unit("meter", vec!["m"], false).unwrap();
+    /// graph.add_unit("kilometer", vec!["km"], false).unwrap();
+    /// graph.add_unit("mile", vec!["mi"], false).unwrap();
+    /// graph.add_edge("m", "km", 0.001, 0.0).unwrap();
+    /// graph.add_edge("km", "mi", 0.621371, 0.0).unwrap();
+    /// graph.add_edge("mi", "m", 1600.0, 0.0).unwrap();
+    ///
+    /// let cycles = graph.check_consistency(1e-6);
+    /// assert_eq!(cycles.len(), 1);
+    /// assert_eq!(cycles[0].units().len(), 4);
+    /// assert!(graph.check_consistency(0.01).is_empty());
+    /// ```
+    pub fn check_consistency(&self, tolerance: f64) -> Vec<InconsistentCycle> {
+        let mut cycles = Vec::new();
+        for tree in self.spanning_forest() {
+            let in_tree = |a: &str, b: &str| tree.parents.get(a) == Some(&b) || tree.parents.get(b) == Some(&a);
+            for &from in &tree.order {
+                let Some(edges) = self.edges.get(from) else {
+                    continue;
+                };
+                // every edge is stored both ways, so look at each pair once
+                for to in edges.keys().map(String::as_str).filter(|&to| from < to && !in_tree(from, to)) {
+                    let mut units = vec![from];
+                    units.extend(tree_path(&tree.parents, to, from));
+                    let factor = units.windows(2)
+                        .fold(ConversionFactor::identity(), |total, pair| total.then(&self.edges[pair[0]][pair[1]].factor));
+                    if !factor.is_identity(tolerance) {
+                        cycles.push(InconsistentCycle {
+                            units: units.into_iter().map(str::to_string).collect(),
+                            factor,
+                        });
+                    }
+                }
+            }
+        }
+        cycles
+    }
+}
+
+/// The path between two units of the same search tree, both ends included.
+fn tree_path<'a>(parents: &HashMap<&'a str, &'a str>, from: &'a str, to: &'a str) -> Vec<&'a str> {
+    let to_root = |mut unit: &'a str| {
+        let mut path = vec![unit];
+        while let Some(&parent) = parents.get(unit) {
+            path.push(parent);
+            unit = parent;
+        }
+        path
+    };
+    let up = to_root(from);
+    let mut down = to_root(to);
+    // drop the common ancestors, keeping the lowest one in `up`
+    let shared: HashSet<&str> = down.iter().copied().collect();
+    let meet = up.iter().position(|unit| shared.contains(unit)).expect("units of one tree share its root");
+    let lowest = up[meet];
+    down.truncate(down.iter().position(|&unit| unit == lowest).expect("ancestor is on both paths"));
+    down.reverse();
+    up[..=meet].iter().copied().chain(down).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+
+    fn length_graph(mile_to_meter: f64) -> ConversionGraph {
+        let mut graph = ConversionGraph::new();
+        let _ = graph.add_unit("meter", vec!["m"], false);
+        let _ = graph.add_unit("kilometer", vec!["km"], false);
+        let _ = graph.add_unit("mile", vec!["mi"], false);
+        let _ = graph.add_unit("foot", vec!["ft"], false);
+        let _ = graph.add_edge("m", "km", 0.001, 0.0);
+        let _ = graph.add_edge("km", "mi", 0.621371192237334, 0.0);
+        let _ = graph.add_edge("mi", "m", mile_to_meter, 0.0);
+        let _ = graph.add_edge("mi", "ft", 5280.0, 0.0);
+        graph
+    }
+
+    #[test]
+    fn test_check_consistency_consistent_graph() {
+        assert!(length_graph(1609.344).check_consistency(1e-9).is_empty());
+    }
+
+    #[test]
+    fn test_check_consistency_reports_cycle_edges() {
+        let graph = length_graph(1600.0);
+        let cycles = graph.check_consistency(1e-9);
+        assert_eq!(cycles.len(), 1);
+        let cycle = &cycles[0];
+        assert_eq!(cycle.units().first(), cycle.units().last());
+        assert_eq!(cycle.edges().count(), 3);
+        assert_eq!(cycle.units(), ["kilometer", "meter", "mile", "kilometer"]);
+        let edges: Vec<(&str, &str)> = cycle.edges().collect();
+        assert_eq!(edges[0], ("kilometer", "meter"));
+        assert_relative_eq!(cycle.factor().scale(), 1609.344 / 1600.0, max_relative = 1e-6);
+    }
+
+    #[test]
+    fn test_check_consistency_affine_cycle() {
+        let mut graph = ConversionGraph::new();
+        let _ = graph.add_unit("Celsius", vec!["C"], false);
+        let _ = graph.add_unit("Kelvin", vec!["K"], false);
+        let _ = graph.add_unit("Fahrenheit", vec!["F"], false);
+        let _ = graph.add_edge("C", "K", 1.0, 273.15);
+        let _ = graph.add_edge("K", "F", 1.8, -459.67);
+        let _ = graph.add_edge("C", "F", 1.8, 32.0);
+        assert!(graph.check_consistency(1e-9).is_empty());
+        let _ = graph.add_edge("C", "F", 1.8, 31.0);
+        assert_eq!(graph.check_consistency(1e-9).len(), 1);
+    }
+
+    #[test]
+    fn test_check_consistency_independent_cycles() {
+        let mut graph = length_graph(1600.0);
+        let _ = graph.add_unit("yard", vec!["yd"], false);
+        let _ = graph.add_edge("ft", "yd", 1.0 / 3.0, 0.0);
+        let _ = graph.add_edge("yd", "mi", 1.0 / 1700.0, 0.0);
+        assert_eq!(graph.check_consistency(1e-9).len(), 2);
+    }
+}
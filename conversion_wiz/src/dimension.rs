@@ -0,0 +1,307 @@
+//! Physical dimensions of units.
+
+use std::fmt;
+use std::ops::{Div, Mul};
+use std::str::FromStr;
+
+use crate::ConversionError;
+
+/// `BaseDimension` enum to represent the base quantities every dimension is built from.
+/// These are the seven SI base quantities plus information (bits, bytes).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BaseDimension {
+    Length,
+    Mass,
+    Time,
+    Current,
+    Temperature,
+    Amount,
+    Luminosity,
+    Information,
+}
+
+impl BaseDimension {
+    pub const ALL: [BaseDimension; 8] = [
+        BaseDimension::Length,
+        BaseDimension::Mass,
+        BaseDimension::Time,
+        BaseDimension::Current,
+        BaseDimension::Temperature,
+        BaseDimension::Amount,
+        BaseDimension::Luminosity,
+        BaseDimension::Information,
+    ];
+
+    /// The name used when parsing and displaying dimensions, e.g. "length".
+    pub fn name(&self) -> &'static str {
+        match self {
+            BaseDimension::Length => "length",
+            BaseDimension::Mass => "mass",
+            BaseDimension::Time => "time",
+            BaseDimension::Current => "current",
+            BaseDimension::Temperature => "temperature",
+            BaseDimension::Amount => "amount",
+            BaseDimension::Luminosity => "luminosity",
+            BaseDimension::Information => "information",
+        }
+    }
+
+    fn index(&self) -> usize {
+        *self as usize
+    }
+}
+
+/// Derived dimensions that can be referred to by name when parsing.
+const NAMED_DIMENSIONS: [(&str, &str); 12] = [
+    ("area", "length^2"),
+    ("volume", "length^3"),
+    ("velocity", "length/time"),
+    ("speed", "length/time"),
+    ("acceleration", "length/time^2"),
+    ("frequency", "1/time"),
+    ("force", "mass*length/time^2"),
+    ("pressure", "mass/length/time^2"),
+    ("energy", "mass*length^2/time^2"),
+    ("power", "mass*length^2/time^3"),
+    ("charge", "current*time"),
+    ("voltage", "mass*length^2/time^3/current"),
+];
+
+/// `Dimension` struct to represent the physical dimension of a unit as a vector of
+/// exponents over the `BaseDimension`s, e.g. velocity is length^1 * time^-1.
+/// Only units with equal dimensions can be converted into each other.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Dimension {
+    exponents: [i8; 8],
+}
+
+impl Dimension {
+    /// The dimension of pure numbers (all exponents zero).
+    pub fn dimensionless() -> Self {
+        Self::default()
+    }
+
+    /// The dimension of a single base quantity, e.g. `Dimension::base(BaseDimension::Length)`.
+    pub fn base(base: BaseDimension) -> Self {
+        let mut dimension = Self::default();
+        dimension.exponents[base.index()] = 1;
+        dimension
+    }
+
+    /// The exponent of one base quantity.
+    pub fn exponent(&self, base: BaseDimension) -> i8 {
+        self.exponents[base.index()]
+    }
+
+    pub fn is_dimensionless(&self) -> bool {
+        self.exponents.iter().all(|&e| e == 0)
+    }
+
+    /// Raise the dimension to an integer power.
+    ///
+    /// # Panics
+    ///
+    /// Panics if an exponent overflows `i8`; see `checked_powi`.
+    pub fn powi(&self, power: i8) -> Self {
+        self.checked_powi(power).expect("dimension exponent overflow")
+    }
+
+    /// Raise the dimension to an integer power, or `None` if an exponent overflows `i8`.
+    pub fn checked_powi(&self, power: i8) -> Option<Self> {
+        let mut dimension = *self;
+        for e in dimension.exponents.iter_mut() {
+            *e = e.checked_mul(power)?;
+        }
+        Some(dimension)
+    }
+
+    /// The product of two dimensions, or `None` if an exponent overflows `i8`.
+    pub fn checked_mul(&self, rhs: Dimension) -> Option<Self> {
+        // multiplying quantities adds the exponents of their dimensions
+        let mut dimension = *self;
+        for (e, r) in dimension.exponents.iter_mut().zip(rhs.exponents) {
+            *e = e.checked_add(r)?;
+        }
+        Some(dimension)
+    }
+
+    /// The quotient of two dimensions, or `None` if an exponent overflows `i8`.
+    pub fn checked_div(&self, rhs: Dimension) -> Option<Self> {
+        self.checked_mul(rhs.checked_powi(-1)?)
+    }
+}
+
+/// # Panics
+///
+/// Panics if an exponent overflows `i8`; see `Dimension::checked_mul`.
+impl Mul for Dimension {
+    type Output = Dimension;
+
+    fn mul(self, rhs: Dimension) -> Dimension {
+        self.checked_mul(rhs).expect("dimension exponent overflow")
+    }
+}
+
+/// # Panics
+///
+/// Panics if an exponent overflows `i8`; see `Dimension::checked_div`.
+impl Div for Dimension {
+    type Output = Dimension;
+
+    fn div(self, rhs: Dimension) -> Dimension {
+        self.checked_div(rhs).expect("dimension exponent overflow")
+    }
+}
+
+/// Parses expressions such as `length`, `length/time^2`, `mass*length^2/time^2`,
+/// `1/time` or named derived dimensions such as `velocity` and `energy`.
+/// Operators apply left to right, so `mass/length/time^2` is `mass/(length*time^2)`.
+/// An expression whose exponents overflow `i8` is an invalid dimension.
+impl FromStr for Dimension {
+    type Err = ConversionError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || ConversionError::InvalidDimension(s.to_string());
+        let s = s.trim();
+        if s.is_empty() {
+            return Err(invalid());
+        }
+        if s.eq_ignore_ascii_case("dimensionless") {
+            return Ok(Self::dimensionless());
+        }
+
+        let mut dimension = Self::dimensionless();
+        let mut divide = false;
+        let mut rest = s;
+        loop {
+            let end = rest.find(['*', '/', '·']).unwrap_or(rest.len());
+            let (term, power) = match rest[..end].split_once('^') {
+                Some((term, power)) => (term.trim(), power.trim().parse::<i16>().map_err(|_| invalid())?),
+                None => (rest[..end].trim(), 1),
+            };
+            let term: Self = match term {
+                "1" => Self::dimensionless(),
+                _ => {
+                    let lower = term.to_ascii_lowercase();
+                    if let Some(base) = BaseDimension::ALL.iter().find(|b| b.name() == lower) {
+                        Self::base(*base)
+                    } else if let Some((_, expr)) = NAMED_DIMENSIONS.iter().find(|(name, _)| *name == lower) {
+                        expr.parse()?
+                    } else {
+                        return Err(invalid());
+                    }
+                }
+            };
+            // a divisor is raised to the negated power, so `1/length^128` is `length^-128`
+            let power = i8::try_from(if divide { -power } else { power }).map_err(|_| invalid())?;
+            let term = term.checked_powi(power).ok_or_else(invalid)?;
+            dimension = dimension.checked_mul(term).ok_or_else(invalid)?;
+
+            let Some(op) = rest[end..].chars().next() else {
+                break;
+            };
+            divide = op == '/';
+            rest = &rest[end + op.len_utf8()..];
+        }
+        Ok(dimension)
+    }
+}
+
+/// Displays the dimension in the form accepted by `FromStr`, e.g. `mass*length/time^2`.
+impl fmt::Display for Dimension {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_dimensionless() {
+            return write!(f, "dimensionless");
+        }
+        let term = |base: &BaseDimension, e: i8| match e.unsigned_abs() {
+            1 => base.name().to_string(),
+            p => format!("{}^{}", base.name(), p),
+        };
+        let numerator: Vec<String> = BaseDimension::ALL.iter()
+            .filter(|b| self.exponent(**b) > 0)
+            .map(|b| term(b, self.exponent(*b)))
+            .collect();
+        let denominator: Vec<String> = BaseDimension::ALL.iter()
+            .filter(|b| self.exponent(**b) < 0)
+            .map(|b| term(b, self.exponent(*b)))
+            .collect();
+
+        if numerator.is_empty() {
+            write!(f, "1")?;
+        } else {
+            write!(f, "{}", numerator.join("*"))?;
+        }
+        for term in denominator {
+            write!(f, "/{}", term)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dimension_parse_base() {
+        let length: Dimension = "length".parse().unwrap();
+        assert_eq!(length, Dimension::base(BaseDimension::Length));
+        assert_eq!("Temperature".parse::<Dimension>().unwrap(), Dimension::base(BaseDimension::Temperature));
+        assert!("dimensionless".parse::<Dimension>().unwrap().is_dimensionless());
+    }
+
+    #[test]
+    fn test_dimension_parse_expression() {
+        let force: Dimension = "mass*length/time^2".parse().unwrap();
+        assert_eq!(force.exponent(BaseDimension::Mass), 1);
+        assert_eq!(force.exponent(BaseDimension::Length), 1);
+        assert_eq!(force.exponent(BaseDimension::Time), -2);
+        assert_eq!("force".parse::<Dimension>().unwrap(), force);
+        assert_eq!("mass/length/time^2".parse::<Dimension>().unwrap(), "pressure".parse().unwrap());
+        assert_eq!("1/time".parse::<Dimension>().unwrap(), Dimension::base(BaseDimension::Time).powi(-1));
+    }
+
+    #[test]
+    fn test_dimension_parse_invalid() {
+        assert!(matches!("".parse::<Dimension>(), Err(ConversionError::InvalidDimension(_))));
+        assert!(matches!("lenght".parse::<Dimension>(), Err(ConversionError::InvalidDimension(_))));
+        assert!(matches!("length^x".parse::<Dimension>(), Err(ConversionError::InvalidDimension(_))));
+        assert!(matches!("length/".parse::<Dimension>(), Err(ConversionError::InvalidDimension(_))));
+    }
+
+    #[test]
+    fn test_dimension_exponent_overflow() {
+        for expr in ["length^100*length^100", "1/length^-128", "length^-128/length", "energy^100"] {
+            assert!(matches!(expr.parse::<Dimension>(), Err(ConversionError::InvalidDimension(_))), "{}", expr);
+        }
+        assert_eq!("length^127".parse::<Dimension>().unwrap().exponent(BaseDimension::Length), 127);
+        assert_eq!("1/length^127".parse::<Dimension>().unwrap().exponent(BaseDimension::Length), -127);
+        let length = Dimension::base(BaseDimension::Length);
+        assert!(length.checked_powi(100).unwrap().checked_mul(length.checked_powi(100).unwrap()).is_none());
+        assert!(length.checked_powi(-128).unwrap().checked_powi(-1).is_none());
+    }
+
+    #[test]
+    fn test_dimension_display_round_trip() {
+        for expr in ["length", "mass*length/time^2", "1/time", "length^3", "dimensionless", "energy"] {
+            let dimension: Dimension = expr.parse().unwrap();
+            assert_eq!(dimension.to_string().parse::<Dimension>().unwrap(), dimension);
+        }
+        assert_eq!("velocity".parse::<Dimension>().unwrap().to_string(), "length/time");
+    }
+
+    #[test]
+    fn test_dimension_display_round_trip_extreme_exponents() {
+        for (expr, display) in [
+            ("length^127", "length^127"),
+            ("1/length^127", "1/length^127"),
+            ("length^-128", "1/length^128"),
+            ("1/length^128", "1/length^128"),
+            ("mass^127/time^128", "mass^127/time^128"),
+        ] {
+            let dimension: Dimension = expr.parse().unwrap();
+            assert_eq!(dimension.to_string(), display);
+            assert_eq!(display.parse::<Dimension>().unwrap(), dimension);
+        }
+    }
+}
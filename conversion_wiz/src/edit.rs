@@ -0,0 +1,303 @@
+//! Removing and renaming units, aliases and conversions.
+
+use std::collections::{HashSet, VecDeque};
+
+use crate::{ConversionError, ConversionGraph, Unit};
+
+/// Editing an existing graph.
+///
+/// Edits take units by their exact name or alias only. Prefixed aliases such as `km` and
+/// the lenient spellings of the graph's `UnitLookup` (such as `℃` for `°C`) are not
+/// accepted, so an edit only ever changes the unit it names.
+impl ConversionGraph {
+    /// Remove a unit, given by exact name or alias, together with all of its aliases and
+    /// conversions. Returns the removed unit.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ConversionError::UnitNotFound` if no unit has the name or alias.
+    /// Returns `ConversionError::OrphanedIntermediateUnit` if an intermediate unit connected
+    /// to the unit could no longer reach any visible unit; the graph is left unchanged.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use conversion_wiz::ConversionGraph;
+    ///
+    /// let mut graph = ConversionGraph::new();
+    /// graph.add_unit("meter", vec!["m"], false).unwrap();
+    /// graph.add_unit("foot", vec!["ft"], false).unwrap();
+    /// graph.add_edge("ft", "m", 0.3048, 0.0).unwrap();
+    /// let foot = graph.remove_unit("ft").unwrap();
+    /// assert_eq!(foot.name(), "foot");
+    /// assert!(!graph.contains_unit("foot"));
+    /// ```
+    pub fn remove_unit(&mut self, alias: &str) -> Result<Unit, ConversionError> {
+        let name = self.edited_unit(alias)?;
+        let neighbors: Vec<&String> = self.edges.get(&name).map(|edges| edges.keys().collect()).unwrap_or_default();
+        for neighbor in neighbors {
+            self.check_not_orphaned(neighbor, Some(&name), None)?;
+        }
+
+        if let Some(edges) = self.edges.remove(&name) {
+            for neighbor in edges.keys() {
+                self.remove_directed_edge(neighbor, &name);
+            }
+        }
+        let unit = self.name_to_units.remove(&name).expect("aliases point to existing units");
+        for alias in unit.aliases() {
+            self.aliases_to_name.remove(alias);
+        }
+        self.alias_table.take();
+        Ok(unit)
+    }
+
+    /// Remove one alias of a unit. The canonical name of a unit cannot be removed;
+    /// use `rename_unit` or `remove_unit` instead.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ConversionError::UnitNotFound` if no unit has exactly this alias.
+    /// Returns `ConversionError::CannotRemoveUnitName` if the alias is the name of its unit.
+    pub fn remove_alias(&mut self, alias: &str) -> Result<(), ConversionError> {
+        let name = self.edited_unit(alias)?;
+        if name == alias {
+            return Err(ConversionError::CannotRemoveUnitName(alias.to_string()));
+        }
+        let unit = self.name_to_units.get_mut(&name).expect("aliases point to existing units");
+        unit.aliases.retain(|a| a != alias);
+        self.aliases_to_name.remove(alias);
+        self.alias_table.take();
+        Ok(())
+    }
+
+    /// Remove the conversion between two units, given by exact name or alias, in both
+    /// directions.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ConversionError::UnitNotFound` if no unit has exactly one of the aliases.
+    /// Returns `ConversionError::EdgeNotFound` if the units have no direct conversion.
+    /// Returns `ConversionError::OrphanedIntermediateUnit` if an intermediate unit could no
+    /// longer reach any visible unit; the graph is left unchanged.
+    pub fn remove_edge(&mut self, from: &str, to: &str) -> Result<(), ConversionError> {
+        let from_name = self.edited_unit(from)?;
+        let to_name = self.edited_unit(to)?;
+        if !self.edges.get(&from_name).is_some_and(|edges| edges.contains_key(&to_name)) {
+            return Err(ConversionError::EdgeNotFound(from.to_string(), to.to_string()));
+        }
+        for unit in [&from_name, &to_name] {
+            self.check_not_orphaned(unit, None, Some((&from_name, &to_name)))?;
+        }
+
+        self.remove_directed_edge(&from_name, &to_name);
+        self.remove_directed_edge(&to_name, &from_name);
+        Ok(())
+    }
+
+    /// Give a unit a new canonical name. The old name stops being an alias; the other
+    /// aliases and all conversions are kept.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ConversionError::UnitNotFound` if no unit has exactly the name or alias `old`.
+    /// Returns `ConversionError::EmptyUnitName` if the new name is empty.
+    /// Returns `ConversionError::DuplicateAlias` if the new name is an alias of another unit.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use conversion_wiz::ConversionGraph;
+    ///
+    /// let mut graph = ConversionGraph::new();
+    /// graph.add_unit("metre", vec!["m"], false).unwrap();
+    /// graph.rename_unit("metre", "meter").unwrap();
+    /// assert_eq!(graph.unit("m").unwrap().name(), "meter");
+    /// assert!(!graph.contains_unit("metre"));
+    /// ```
+    pub fn rename_unit(&mut self, old: &str, new: &str) -> Result<(), ConversionError> {
+        let old_name = self.edited_unit(old)?;
+        if new.is_empty() {
+            return Err(ConversionError::EmptyUnitName);
+        }
+        if self.aliases_to_name.get(new).is_some_and(|name| *name != old_name) {
+            return Err(ConversionError::DuplicateAlias(new.to_string()));
+        }
+        if old_name == new {
+            return Ok(());
+        }
+
+        let mut unit = self.name_to_units.remove(&old_name).expect("aliases point to existing units");
+        unit.name = new.to_string();
+        unit.aliases.retain(|alias| *alias != old_name);
+        if !unit.aliases.iter().any(|alias| alias == new) {
+            unit.aliases.push(new.to_string());
+        }
+        self.aliases_to_name.remove(&old_name);
+        for alias in unit.aliases() {
+            self.aliases_to_name.insert(alias.clone(), new.to_string());
+        }
+        self.alias_table.take();
+        self.name_to_units.insert(new.to_string(), unit);
+
+        if let Some(edges) = self.edges.remove(&old_name) {
+            for neighbor in edges.keys() {
+                if let Some(edge) = self.edges.get_mut(neighbor).and_then(|back| back.remove(&old_name)) {
+                    self.edges.get_mut(neighbor).expect("neighbor has edges").insert(new.to_string(), edge);
+                }
+            }
+            self.edges.insert(new.to_string(), edges);
+        }
+        Ok(())
+    }
+
+    /// The name of the unit with exactly the name or alias `alias`.
+    fn edited_unit(&self, alias: &str) -> Result<String, ConversionError> {
+        self.aliases_to_name.get(alias)
+            .cloned()
+            .ok_or_else(|| ConversionError::unit_not_found(alias))
+    }
+
+    /// Remove the edge `from -> to` only, dropping the edge map of `from` once it is empty.
+    fn remove_directed_edge(&mut self, from: &str, to: &str) {
+        if let Some(edges) = self.edges.get_mut(from) {
+            edges.remove(to);
+            if edges.is_empty() {
+                self.edges.remove(from);
+            }
+        }
+    }
+
+    /// Fail if `unit` is intermediate and, without `removed_unit` or `removed_edge`,
+    /// would no longer be connected to any unit that is not intermediate.
+    fn check_not_orphaned(&self, unit: &str, removed_unit: Option<&str>, removed_edge: Option<(&str, &str)>) -> Result<(), ConversionError> {
+        if !self.name_to_units.get(unit).is_some_and(|u| u.intermediate) {
+            return Ok(());
+        }
+        let removed = |a: &str, b: &str| removed_edge.is_some_and(|(from, to)| (a, b) == (from, to) || (a, b) == (to, from));
+
+        let mut visited: HashSet<&str> = HashSet::from([unit]);
+        let mut queue = VecDeque::from([unit]);
+        while let Some(current) = queue.pop_front() {
+            if !self.name_to_units[current].intermediate {
+                return Ok(());
+            }
+            for next in self.edges.get(current).into_iter().flat_map(|edges| edges.keys()) {
+                if Some(next.as_str()) != removed_unit && !removed(current, next) && visited.insert(next) {
+                    queue.push_back(next);
+                }
+            }
+        }
+        Err(ConversionError::OrphanedIntermediateUnit(unit.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+
+    const REL_TOL: f64 = 1e-9;
+
+    /// Kelvin - k1 - k2 - Fahrenheit, with k1 and k2 intermediate, plus Celsius - Kelvin
+    fn temperature_graph() -> ConversionGraph {
+        let mut graph = ConversionGraph::new();
+        let _ = graph.add_unit("Kelvin", vec!["K"], false);
+        let _ = graph.add_unit("Celsius", vec!["C", "degC"], false);
+        let _ = graph.add_unit("Fahrenheit", vec!["F"], false);
+        let _ = graph.add_unit("k1", vec![], true);
+        let _ = graph.add_unit("k2", vec![], true);
+        let _ = graph.add_edge("C", "K", 1.0, 273.15);
+        let _ = graph.add_edge("K", "k1", 1.0, -273.15);
+        let _ = graph.add_edge("k1", "k2", 1.8, 0.0);
+        let _ = graph.add_edge("k2", "F", 1.0, 32.0);
+        graph
+    }
+
+    #[test]
+    fn test_remove_unit() {
+        let mut graph = temperature_graph();
+        let celsius = graph.remove_unit("degC").unwrap();
+        assert_eq!(celsius.name(), "Celsius");
+        assert!(!graph.contains_unit("C") && !graph.contains_unit("Celsius"));
+        assert!(graph.edges.get("Kelvin").is_some_and(|edges| !edges.contains_key("Celsius")));
+        assert_relative_eq!(graph.convert("K", "F", 273.15).unwrap(), 32.0, max_relative = REL_TOL);
+        assert!(matches!(graph.remove_unit("C"), Err(ConversionError::UnitNotFound { .. })));
+    }
+
+    #[test]
+    fn test_remove_unit_would_orphan_intermediate() {
+        let mut graph = temperature_graph();
+        // k1 and k2 can still reach Kelvin
+        graph.remove_unit("F").unwrap();
+        assert!(matches!(graph.remove_unit("K"), Err(ConversionError::OrphanedIntermediateUnit(unit)) if unit == "k1"));
+        assert!(graph.contains_unit("K"));
+        // removing the intermediate units first is fine
+        graph.remove_unit("k2").unwrap();
+        graph.remove_unit("k1").unwrap();
+        graph.remove_unit("K").unwrap();
+        assert!(graph.edges.is_empty());
+    }
+
+    #[test]
+    fn test_remove_edge() {
+        let mut graph = temperature_graph();
+        graph.remove_edge("K", "C").unwrap();
+        assert!(matches!(graph.convert("C", "F", 0.0), Err(ConversionError::ConversionPathNotFound(_, _))));
+        assert!(matches!(graph.remove_edge("C", "K"), Err(ConversionError::EdgeNotFound(_, _))));
+        assert!(!graph.edges.contains_key("Celsius"));
+
+        graph.remove_edge("k1", "k2").unwrap();
+        assert!(matches!(graph.remove_edge("K", "k1"), Err(ConversionError::OrphanedIntermediateUnit(unit)) if unit == "k1"));
+    }
+
+    #[test]
+    fn test_remove_alias() {
+        let mut graph = temperature_graph();
+        graph.remove_alias("degC").unwrap();
+        assert!(!graph.contains_unit("degC"));
+        assert_eq!(graph.unit("C").unwrap().aliases(), ["C", "Celsius"]);
+        assert!(matches!(graph.remove_alias("Celsius"), Err(ConversionError::CannotRemoveUnitName(_))));
+        assert!(matches!(graph.remove_alias("degC"), Err(ConversionError::UnitNotFound { .. })));
+    }
+
+    #[test]
+    fn test_rename_unit() {
+        let mut graph = temperature_graph();
+        graph.rename_unit("C", "degree Celsius").unwrap();
+        assert!(!graph.contains_unit("Celsius"));
+        assert_eq!(graph.unit("degC").unwrap().name(), "degree Celsius");
+        assert_eq!(graph.path("C", "K").unwrap(), vec!["degree Celsius", "Kelvin"]);
+        assert_relative_eq!(graph.convert("degree Celsius", "F", 100.0).unwrap(), 212.0, max_relative = REL_TOL);
+        assert!(matches!(graph.rename_unit("C", "K"), Err(ConversionError::DuplicateAlias(_))));
+        assert!(matches!(graph.rename_unit("C", ""), Err(ConversionError::EmptyUnitName)));
+        // an existing alias of the same unit can become its name
+        graph.rename_unit("degC", "C").unwrap();
+        assert_eq!(graph.unit("C").unwrap().name(), "C");
+    }
+
+    #[test]
+    fn test_edits_use_exact_aliases() {
+        let mut graph = ConversionGraph::new();
+        let _ = graph.add_prefixable_unit("meter", vec!["m"], false);
+        let _ = graph.add_unit("foot", vec!["ft"], false);
+        let _ = graph.add_unit("Celsius", vec!["°C"], false);
+        let _ = graph.add_unit("Kelvin", vec!["K"], false);
+        let _ = graph.add_edge("ft", "m", 0.3048, 0.0);
+        let _ = graph.add_edge("°C", "K", 1.0, 273.15);
+        // both are accepted by conversions
+        assert!(graph.contains_unit("km") && graph.contains_unit("℃"));
+
+        let not_found = |result: Result<(), ConversionError>| matches!(result, Err(ConversionError::UnitNotFound { .. }));
+        assert!(not_found(graph.remove_edge("ft", "km")));
+        assert!(not_found(graph.remove_edge("℃", "K")));
+        assert!(not_found(graph.remove_unit("℃").map(|_| ())));
+        assert!(not_found(graph.remove_alias("℃")));
+        assert!(not_found(graph.rename_unit("℃", "degC")));
+        assert_eq!(graph.edges.len(), 4);
+
+        graph.remove_edge("ft", "m").unwrap();
+        graph.remove_unit("°C").unwrap();
+        assert_eq!(graph.edges.len(), 0);
+    }
+}
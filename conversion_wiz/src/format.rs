@@ -0,0 +1,395 @@
+//! Formatting numbers for display: precision, notation and separators.
+
+use num_bigint::BigInt;
+use num_rational::BigRational;
+use num_traits::{Signed, Zero};
+use serde::{Deserialize, Serialize};
+
+use crate::rational::{format_decimal, rational_from_f64, MAX_DECIMAL_PLACES};
+use crate::ConversionGraph;
+
+/// Significant figures kept by `Precision::Auto`: as many as an `f64` reliably holds.
+const AUTO_SIGNIFICANT_FIGURES: usize = 15;
+
+/// `Precision` enum to represent how many digits a formatted number shows.
+/// In a config it is written `"auto"`, `{ "decimals": 2 }` or `{ "significant_figures": 4 }`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Precision {
+    /// up to 15 significant figures with trailing zeros dropped, which hides float noise
+    /// such as `59.00000000000001`
+    #[default]
+    Auto,
+    /// a fixed number of digits after the decimal point
+    Decimals(usize),
+    /// a fixed number of significant figures
+    SignificantFigures(usize),
+}
+
+/// `Notation` enum to represent how the magnitude of a formatted number is written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Notation {
+    /// all digits, e.g. `12345.6`
+    #[default]
+    Plain,
+    /// one digit before the decimal point and a power of ten, e.g. `1.23456e4`
+    Scientific,
+    /// one to three digits before the decimal point and a power of ten that is a
+    /// multiple of three, e.g. `12.3456e3`
+    Engineering,
+}
+
+/// `NumberFormat` struct to describe how to write numbers: the precision, the notation,
+/// and the decimal and digit group separators.
+///
+/// Numbers are rounded in decimal, halves away from zero, and written with as many digits
+/// as asked for. A float is first read as the shortest decimal that rounds to it, so
+/// `2.675` to two decimals is `2.68`.
+///
+/// # Examples
+///
+/// ```
+/// use conversion_wiz::{Notation, NumberFormat, Precision};
+///
+/// assert_eq!(NumberFormat::new().format(59.00000000000001), "59");
+/// let two_decimals = NumberFormat::new().with_precision(Precision::Decimals(2));
+/// assert_eq!(two_decimals.format(1234.5), "1234.50");
+/// let german = NumberFormat::for_locale("de-DE").unwrap().with_precision(Precision::Decimals(2));
+/// assert_eq!(german.format(1234.5), "1.234,50");
+/// let engineering = NumberFormat::new()
+///     .with_precision(Precision::SignificantFigures(3))
+///     .with_notation(Notation::Engineering);
+/// assert_eq!(engineering.format(0.000123456), "123e-6");
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct NumberFormat {
+    /// `None` leaves the choice to the unit (see `ConversionGraph::format_value`)
+    precision: Option<Precision>,
+    notation: Notation,
+    decimal_separator: char,
+    group_separator: Option<char>,
+}
+
+impl Default for NumberFormat {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl NumberFormat {
+    /// Plain notation with a `.` decimal separator, no digit grouping and the precision of
+    /// the unit, or `Precision::Auto` without one.
+    pub fn new() -> Self {
+        Self {
+            precision: None,
+            notation: Notation::Plain,
+            decimal_separator: '.',
+            group_separator: None,
+        }
+    }
+
+    /// The separators used in a locale, given as a language tag such as `en`, `de-DE` or
+    /// `fr_CA`, following the common conventions of CLDR. `None` for unknown languages.
+    pub fn for_locale(tag: &str) -> Option<Self> {
+        let tag = tag.to_ascii_lowercase().replace('_', "-");
+        let language = tag.split('-').next().unwrap_or_default();
+        let (decimal, group) = match language {
+            _ if tag == "de-ch" || tag == "it-ch" => ('.', '\''),
+            "en" | "ja" | "zh" | "ko" | "he" | "th" | "hi" => ('.', ','),
+            "de" | "es" | "it" | "nl" | "pt" | "id" | "tr" | "da" | "el" | "ro" => (',', '.'),
+            "fr" | "ru" | "pl" | "cs" | "sk" | "sv" | "fi" | "nb" | "no" | "uk" | "hu" | "bg" => (',', '\u{a0}'),
+            _ => return None,
+        };
+        Some(Self::new().with_separators(decimal, Some(group)))
+    }
+
+    pub fn with_precision(mut self, precision: Precision) -> Self {
+        self.precision = Some(precision);
+        self
+    }
+
+    pub fn with_notation(mut self, notation: Notation) -> Self {
+        self.notation = notation;
+        self
+    }
+
+    /// Set the decimal separator, and the separator between groups of three digits
+    /// before it (`None` for no grouping).
+    pub fn with_separators(mut self, decimal: char, group: Option<char>) -> Self {
+        self.decimal_separator = decimal;
+        self.group_separator = group;
+        self
+    }
+
+    /// The precision set with `with_precision`, if any.
+    pub fn precision(&self) -> Option<Precision> {
+        self.precision
+    }
+
+    pub fn notation(&self) -> Notation {
+        self.notation
+    }
+
+    /// Write a float. Infinite and NaN values are written as `inf`, `-inf` and `NaN`.
+    pub fn format(&self, value: f64) -> String {
+        if !value.is_finite() {
+            return value.to_string();
+        }
+        self.format_rational(&rational_from_f64(value))
+    }
+
+    /// Write an exact value, such as a result of `ConversionGraph::convert_rational`,
+    /// rounding it only once.
+    pub fn format_rational(&self, value: &BigRational) -> String {
+        let precision = self.precision.unwrap_or_default();
+        let (digits, exponent) = match self.notation {
+            Notation::Plain => (plain(value, precision), None),
+            Notation::Scientific => scaled(value, precision, 1),
+            Notation::Engineering => scaled(value, precision, 3),
+        };
+        let mut formatted = self.separate(&digits);
+        if let Some(exponent) = exponent {
+            formatted.push_str(&format!("e{}", exponent));
+        }
+        formatted
+    }
+
+    /// Replace the `.` of `digits` and group the digits before it.
+    fn separate(&self, digits: &str) -> String {
+        let (sign, digits) = match digits.strip_prefix('-') {
+            Some(rest) => ("-", rest),
+            None => ("", digits),
+        };
+        let (whole, fraction) = digits.split_once('.').map_or((digits, None), |(w, f)| (w, Some(f)));
+        let mut formatted = sign.to_string();
+        for (i, digit) in whole.chars().enumerate() {
+            if i > 0 && (whole.len() - i) % 3 == 0 {
+                formatted.extend(self.group_separator);
+            }
+            formatted.push(digit);
+        }
+        if let Some(fraction) = fraction {
+            formatted.push(self.decimal_separator);
+            formatted.push_str(fraction);
+        }
+        formatted
+    }
+}
+
+/// Count the significant figures of a number as written: `12.50` has 4 and `0.0012` has 2.
+/// Trailing zeros of a whole number count only with a decimal point, so `1200` has 2 and
+/// `1200.` has 4. An exponent is ignored (`1.20e3` has 3). `None` if the text is not a number.
+///
+/// # Examples
+///
+/// ```
+/// use conversion_wiz::significant_figures;
+///
+/// assert_eq!(significant_figures("12.50"), Some(4));
+/// assert_eq!(significant_figures("-0.00340"), Some(3));
+/// assert_eq!(significant_figures("1200"), Some(2));
+/// ```
+pub fn significant_figures(text: &str) -> Option<usize> {
+    let mantissa = text.trim().split(['e', 'E']).next().unwrap_or_default();
+    let mantissa = mantissa.strip_prefix(['+', '-']).unwrap_or(mantissa);
+    let (whole, fraction) = mantissa.split_once('.').map_or((mantissa, None), |(w, f)| (w, Some(f)));
+    let all_digits = whole.chars().chain(fraction.unwrap_or_default().chars()).all(|c| c.is_ascii_digit());
+    if !all_digits || whole.is_empty() && fraction.is_none_or(str::is_empty) {
+        return None;
+    }
+
+    let digits: String = whole.chars().chain(fraction.unwrap_or_default().chars()).collect();
+    let significant = digits.trim_start_matches('0');
+    if significant.is_empty() {
+        // zero: one figure, or the zeros after the point
+        return Some(fraction.map_or(1, |f| f.len().max(1)));
+    }
+    match fraction {
+        Some(_) => Some(significant.len()),
+        None => Some(significant.trim_end_matches('0').len()),
+    }
+}
+
+/// Formatting.
+impl ConversionGraph {
+    /// Write a value given in a unit. Without a precision set on `format`, the precision
+    /// declared for the unit (or its base unit, for a prefixed unit) is used, and
+    /// `Precision::Auto` without one.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use conversion_wiz::{ConversionGraph, NumberFormat, Precision, Unit};
+    ///
+    /// let mut graph = ConversionGraph::new();
+    /// graph.insert_unit(Unit::new("dollar", vec!["USD"], false).unwrap().with_precision(Precision::Decimals(2))).unwrap();
+    /// assert_eq!(graph.format_value(12.5, "USD", &NumberFormat::new()), "12.50");
+    /// ```
+    pub fn format_value(&self, value: f64, unit: &str, format: &NumberFormat) -> String {
+        self.unit_format(unit, format.clone()).format(value)
+    }
+
+    /// `format`, with the precision declared for `unit` if it sets none itself.
+    pub(crate) fn unit_format(&self, unit: &str, format: NumberFormat) -> NumberFormat {
+        if format.precision.is_some() {
+            return format;
+        }
+        let unit_precision = self.resolve(unit).ok()
+            .and_then(|resolved| self.name_to_units.get(resolved.name))
+            .and_then(|unit| unit.precision());
+        match unit_precision {
+            Some(precision) => format.with_precision(precision),
+            None => format,
+        }
+    }
+}
+
+/// `10^exponent`, exactly.
+fn power_of_ten(exponent: i64) -> BigRational {
+    let power = BigRational::from_integer(num_traits::pow(BigInt::from(10), exponent.unsigned_abs() as usize));
+    if exponent < 0 { power.recip() } else { power }
+}
+
+/// The exponent of the leading digit of a non-zero value: `floor(log10(|value|))`.
+fn leading_exponent(value: &BigRational) -> i64 {
+    let value = value.abs();
+    let mut exponent = value.numer().to_string().len() as i64 - value.denom().to_string().len() as i64;
+    while power_of_ten(exponent) > value {
+        exponent -= 1;
+    }
+    while power_of_ten(exponent + 1) <= value {
+        exponent += 1;
+    }
+    exponent
+}
+
+/// Write `value` with `.` as decimal separator and no grouping.
+fn plain(value: &BigRational, precision: Precision) -> String {
+    match precision {
+        Precision::Decimals(places) => format_decimal(value, places),
+        Precision::SignificantFigures(figures) => significant(value, figures.max(1)),
+        Precision::Auto => {
+            let digits = significant(value, AUTO_SIGNIFICANT_FIGURES);
+            match digits.contains('.') {
+                true => digits.trim_end_matches('0').trim_end_matches('.').to_string(),
+                false => digits,
+            }
+        }
+    }
+}
+
+/// Round `value` to a number of significant figures, at most `MAX_DECIMAL_PLACES`.
+fn significant(value: &BigRational, figures: usize) -> String {
+    let figures = figures.min(MAX_DECIMAL_PLACES);
+    if value.is_zero() {
+        return format_decimal(value, figures - 1);
+    }
+    let mut exponent = leading_exponent(value);
+    loop {
+        let places = figures as i64 - 1 - exponent;
+        let unit = power_of_ten(places);
+        let rounded = (value * &unit).round() / unit;
+        // rounding up may add a digit in front, e.g. 9.96 to 2 figures
+        if leading_exponent(&rounded) > exponent {
+            exponent += 1;
+            continue;
+        }
+        return match places {
+            0.. => format_decimal(&rounded, places as usize),
+            _ => rounded.to_integer().to_string(),
+        };
+    }
+}
+
+/// Write `value` as a mantissa and a power of ten that is a multiple of `step`.
+fn scaled(value: &BigRational, precision: Precision, step: i64) -> (String, Option<i64>) {
+    if value.is_zero() {
+        return (plain(value, precision), Some(0));
+    }
+    let mut exponent = leading_exponent(value).div_euclid(step) * step;
+    loop {
+        let mantissa = value / power_of_ten(exponent);
+        let digits = plain(&mantissa, precision);
+        let whole = digits.trim_start_matches('-').split('.').next().unwrap_or_default();
+        // the mantissa may round up to 10 (or 1000), e.g. 9.996 to 3 figures
+        if whole.len() as i64 > step {
+            exponent += step;
+            continue;
+        }
+        return (digits, Some(exponent));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn with(precision: Precision) -> NumberFormat {
+        NumberFormat::new().with_precision(precision)
+    }
+
+    #[test]
+    fn test_format_auto_hides_float_noise() {
+        let format = NumberFormat::new();
+        assert_eq!(format.format(59.00000000000001), "59");
+        assert_eq!(format.format(0.1 + 0.2), "0.3");
+        assert_eq!(format.format(-273.15), "-273.15");
+        assert_eq!(format.format(1e21), "1000000000000000000000");
+        assert_eq!(format.format(1.5e-7), "0.00000015");
+        assert_eq!(format.format(0.0), "0");
+        assert_eq!(format.format(f64::INFINITY), "inf");
+    }
+
+    #[test]
+    fn test_format_decimals() {
+        assert_eq!(with(Precision::Decimals(2)).format(2.675), "2.68");
+        assert_eq!(with(Precision::Decimals(2)).format(-0.001), "0.00");
+        assert_eq!(with(Precision::Decimals(0)).format(2.5), "3");
+        assert_eq!(with(Precision::Decimals(3)).format(12.0), "12.000");
+    }
+
+    #[test]
+    fn test_format_significant_figures() {
+        let three = with(Precision::SignificantFigures(3));
+        assert_eq!(three.format(3.28084), "3.28");
+        assert_eq!(three.format(0.00123456), "0.00123");
+        assert_eq!(three.format(123456.0), "123000");
+        assert_eq!(three.format(9.996), "10.0");
+        assert_eq!(three.format(-99.96), "-100");
+        assert_eq!(three.format(0.0), "0.00");
+        assert_eq!(with(Precision::SignificantFigures(4)).format(41.0), "41.00");
+    }
+
+    #[test]
+    fn test_format_notations() {
+        let scientific = with(Precision::SignificantFigures(3)).with_notation(Notation::Scientific);
+        assert_eq!(scientific.format(123456.0), "1.23e5");
+        assert_eq!(scientific.format(-0.000999999), "-1.00e-3");
+        assert_eq!(scientific.format(0.0), "0.00e0");
+        let engineering = NumberFormat::new().with_notation(Notation::Engineering);
+        assert_eq!(engineering.format(123456.0), "123.456e3");
+        assert_eq!(engineering.format(0.05), "50e-3");
+        assert_eq!(engineering.format(999.9999999999999999), "1e3");
+        assert_eq!(with(Precision::Decimals(1)).with_notation(Notation::Scientific).format(1500.0), "1.5e3");
+    }
+
+    #[test]
+    fn test_format_separators() {
+        let english = NumberFormat::for_locale("en_US").unwrap();
+        assert_eq!(english.format(1234567.891), "1,234,567.891");
+        assert_eq!(english.format(-123.0), "-123");
+        let french = NumberFormat::for_locale("fr").unwrap().with_precision(Precision::Decimals(1));
+        assert_eq!(french.format(-1234.56), "-1\u{a0}234,6");
+        assert_eq!(NumberFormat::for_locale("de-CH").unwrap().format(12345.5), "12'345.5");
+        assert_eq!(NumberFormat::for_locale("tlh"), None);
+    }
+
+    #[test]
+    fn test_significant_figures() {
+        for (text, figures) in [("12.50", 4), ("0.0012", 2), ("1200", 2), ("1200.", 4), ("1.20e3", 3), ("7", 1), ("0", 1), ("0.00", 2), ("-3.0", 2)] {
+            assert_eq!(significant_figures(text), Some(figures), "{}", text);
+        }
+        assert_eq!(significant_figures("abc"), None);
+        assert_eq!(significant_figures("."), None);
+    }
+}
@@ -1,7 +1,49 @@
-/// Conversion Model
+//! Conversion Model
 
-use std::collections::HashMap;
+mod batch;
+mod catalog;
+mod category;
+mod compiled;
+mod compound;
+mod config;
+mod consistency;
+mod dimension;
+mod edit;
+mod format;
+mod lookup;
+mod parser;
+mod prefix;
+mod rational;
+mod reach;
+mod standard;
+mod table;
+mod validate;
+
+pub use batch::{BatchError, BatchOptions, BatchReport, RowError};
+pub use catalog::{CatalogError, CatalogLoader};
+pub use category::UNCATEGORIZED;
+pub use compiled::CompiledGraph;
+pub use compound::UnitExpr;
+pub use config::{Config, ConfigError, ConfigFormat, ConfigNumber, Conversion, ConversionOffset, ConversionScale, UnitConfig};
+pub use consistency::InconsistentCycle;
+pub use dimension::{BaseDimension, Dimension};
+pub use format::{significant_figures, Notation, NumberFormat, Precision};
+pub use lookup::UnitLookup;
+pub use parser::{ParseError, ParseErrorKind, Quantity, Query};
+pub use prefix::{Prefix, PrefixKind, PREFIXES};
+pub use rational::{format_decimal, parse_rational, RationalFactor, MAX_DECIMAL_PLACES};
+pub use num_rational::BigRational;
+pub use validate::{ConfigValidator, Diagnostic, Severity};
+
+use lookup::AliasTable;
+use prefix::split_prefix;
+use rational::{rational_from_f64, rational_to_f64};
+use num_traits::Zero;
+use std::borrow::Cow;
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BinaryHeap, HashMap, HashSet};
 use std::fmt;
+use std::sync::OnceLock;
 
 /// Define a custom error type for conversion errors.
 #[derive(Debug)]
@@ -10,11 +52,30 @@ pub enum ConversionError {
     EmptyAlias,
     DuplicateUnit(String),
     DuplicateAlias(String),
-    UnitNotFound(String),
+    /// no unit has the alias; `suggestions` are aliases of units with a similar spelling
+    UnitNotFound {
+        unit: String,
+        suggestions: Vec<String>,
+    },
     ConversionRateZero,
-    ConversionRateBothValues,
     ConversionPathNotFound(String, String),
     MissingConversionFactor,
+    InvalidDimension(String),
+    InvalidUnitExpression(String),
+    OffsetUnitInCompound(String),
+    DuplicateEdge(String, String),
+    /// a conversion that cannot be stored: `from`, `to` and the reason
+    InvalidConversion(String, String, String),
+    EdgeNotFound(String, String),
+    CannotRemoveUnitName(String),
+    OrphanedIntermediateUnit(String),
+    InvalidNumber(String),
+    DimensionMismatch {
+        from: String,
+        from_dimension: Dimension,
+        to: String,
+        to_dimension: Dimension,
+    },
 }
 
 impl std::error::Error for ConversionError {}
@@ -26,15 +87,43 @@ impl fmt::Display for ConversionError {
             ConversionError::EmptyAlias => write!(f, "Unit alias cannot be empty"),
             ConversionError::DuplicateUnit(name) => write!(f, "Unit {} already exists", name),
             ConversionError::DuplicateAlias(alias) => write!(f, "Alias {} already exists", alias),
-            ConversionError::UnitNotFound(name) => write!(f, "Cannot find unit {}", name),
+            ConversionError::UnitNotFound { unit, suggestions } => {
+                write!(f, "Cannot find unit {}", unit)?;
+                match suggestions.split_last() {
+                    Some((last, [])) => write!(f, ", did you mean {}?", last),
+                    Some((last, rest)) => write!(f, ", did you mean {} or {}?", rest.join(", "), last),
+                    None => Ok(()),
+                }
+            }
             ConversionError::ConversionRateZero => write!(f, "Conversion rate cannot be 0"),
             ConversionError::ConversionPathNotFound(from, to) => write!(f, "No conversion path found from '{}' to '{}'", from, to),
-            ConversionError::ConversionRateBothValues => write!(f, "One of the conversion rates must be unchaged (1 for scale, 0 for offset)"),
             ConversionError::MissingConversionFactor => write!(f, "Conversion factor missing in the graph"),
+            ConversionError::InvalidDimension(dimension) => write!(f, "Invalid dimension '{}'", dimension),
+            ConversionError::InvalidUnitExpression(expr) => write!(f, "Invalid unit expression '{}'", expr),
+            ConversionError::OffsetUnitInCompound(unit) => write!(f, "Unit {} needs an offset conversion and cannot be used in a compound unit", unit),
+            ConversionError::DuplicateEdge(from, to) => write!(f, "A conversion between {} and {} already exists", from, to),
+            ConversionError::InvalidConversion(from, to, reason) => write!(f, "Invalid conversion from '{}' to '{}': {}", from, to, reason),
+            ConversionError::EdgeNotFound(from, to) => write!(f, "No conversion between '{}' and '{}'", from, to),
+            ConversionError::CannotRemoveUnitName(name) => write!(f, "{} is the name of its unit and cannot be removed as an alias", name),
+            ConversionError::OrphanedIntermediateUnit(unit) => write!(f, "Intermediate unit {} would no longer be connected to any visible unit", unit),
+            ConversionError::InvalidNumber(text) => write!(f, "Invalid number '{}'", text),
+            ConversionError::DimensionMismatch { from, from_dimension, to, to_dimension } => write!(
+                f,
+                "Cannot convert between '{}' ({}) and '{}' ({}): dimensions differ",
+                from, from_dimension, to, to_dimension
+            ),
         }
     }
 }
 
+impl ConversionError {
+    /// `ConversionError::UnitNotFound` for `unit`, without suggestions (see
+    /// `ConversionGraph::with_suggestions`).
+    pub(crate) fn unit_not_found(unit: &str) -> Self {
+        ConversionError::UnitNotFound { unit: unit.to_string(), suggestions: Vec::new() }
+    }
+}
+
 /// `Unit` struct to represent a measurement unit.
 /// It includes the official name of the unit and any aliases it may have.
 pub struct Unit {
@@ -44,6 +133,17 @@ pub struct Unit {
     aliases: Vec<String>,
     /// boolean to indicate if unit is intermediate - i.e. not shown to user
     intermediate: bool,
+    /// The physical dimension of the unit, if declared. Units without a dimension
+    /// can be connected to any other unit.
+    dimension: Option<Dimension>,
+    /// whether SI prefixes (km, mm, µm, ...) can be applied to the unit
+    prefixable: bool,
+    /// whether binary prefixes (Ki, Mi, ...) can be applied to the unit
+    binary_prefixable: bool,
+    /// how values in the unit are displayed by default
+    precision: Option<Precision>,
+    /// the kind of quantity the unit measures, for grouping units, e.g. "length"
+    category: Option<String>,
 }
 
 
@@ -73,9 +173,99 @@ impl Unit {
             name: name.to_string(),
             aliases,
             intermediate,
+            dimension: None,
+            prefixable: false,
+            binary_prefixable: false,
+            precision: None,
+            category: None,
         })
     }
 
+    /// Allow prefixes of the given kind on the unit, so that e.g. `km` or `KiB`
+    /// resolve to the unit without being registered. Can be called once per kind.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use conversion_wiz::{ConversionGraph, PrefixKind, Unit};
+    ///
+    /// let mut graph = ConversionGraph::new();
+    /// let meter = Unit::new("meter", vec!["m"], false).unwrap().with_prefixes(PrefixKind::Si);
+    /// graph.insert_unit(meter).unwrap();
+    /// assert_eq!(graph.convert("km", "mm", 1.0).unwrap(), 1e6);
+    /// ```
+    pub fn with_prefixes(mut self, kind: PrefixKind) -> Self {
+        match kind {
+            PrefixKind::Si => self.prefixable = true,
+            PrefixKind::Binary => self.binary_prefixable = true,
+        }
+        self
+    }
+
+    /// Whether prefixes of the given kind can be applied to the unit.
+    pub fn accepts_prefix(&self, kind: PrefixKind) -> bool {
+        match kind {
+            PrefixKind::Si => self.prefixable,
+            PrefixKind::Binary => self.binary_prefixable,
+        }
+    }
+
+    /// Set the physical dimension of the unit.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use conversion_wiz::{BaseDimension, Dimension, Unit};
+    ///
+    /// let unit = Unit::new("meter", vec!["m"], false).unwrap()
+    ///     .with_dimension(Dimension::base(BaseDimension::Length));
+    /// assert_eq!(unit.dimension(), Some(Dimension::base(BaseDimension::Length)));
+    /// ```
+    pub fn with_dimension(mut self, dimension: Dimension) -> Self {
+        self.dimension = Some(dimension);
+        self
+    }
+
+    /// Get the physical dimension of the unit, if declared.
+    pub fn dimension(&self) -> Option<Dimension> {
+        self.dimension
+    }
+
+    /// Set how values in the unit are displayed when no precision is asked for,
+    /// e.g. two decimals for a currency (see `ConversionGraph::format_value`).
+    pub fn with_precision(mut self, precision: Precision) -> Self {
+        self.precision = Some(precision);
+        self
+    }
+
+    /// Get the display precision of the unit, if declared.
+    pub fn precision(&self) -> Option<Precision> {
+        self.precision
+    }
+
+    /// Set the category of the unit, the kind of quantity it measures (see
+    /// `ConversionGraph::units_by_category`). Unlike the dimension, it is free text, so
+    /// units of the same dimension can be told apart, e.g. "energy" and "torque".
+    pub fn with_category(mut self, category: &str) -> Self {
+        self.category = Some(category.to_string());
+        self
+    }
+
+    /// Get the category of the unit, if declared.
+    pub fn category(&self) -> Option<&str> {
+        self.category.as_deref()
+    }
+
+    /// Whether the unit is intermediate, i.e. hidden from the user.
+    pub fn intermediate(&self) -> bool {
+        self.intermediate
+    }
+
+    /// Get the canonical name of the unit.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
     /// Get the list of aliases for the unit.
     pub fn aliases(&self) -> &[String] {
         &self.aliases
@@ -88,7 +278,7 @@ impl Unit {
         if !self.aliases.is_empty() {
             formatted.push_str(" (");
             formatted.push_str(&self.aliases.join(", "));
-            formatted.push_str(")");
+            formatted.push(')');
         }
         formatted
     }
@@ -96,7 +286,13 @@ impl Unit {
 
 /// `ConversionFactor` struct to represent a conversion rate between two units.
 /// it is used to convert from one unit to another.
-/// It includes a scale factor and an offset.
+/// It is an affine map `y = scale * x + offset`, so a single factor can express
+/// conversions such as Celsius to Fahrenheit (`F = 1.8 * C + 32`).
+///
+/// Factors form an algebra: `identity()` is the neutral element, `then()` composes
+/// two factors into one and `inverse()` undoes a factor. A factor composed over a whole
+/// path can be cached and applied to many values without walking the graph again.
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct ConversionFactor {
     scale: f64, // for multiplication
     offset: f64, // for addition
@@ -110,6 +306,11 @@ impl ConversionFactor {
         }
     }
 
+    /// The factor that leaves every value unchanged (`scale = 1`, `offset = 0`).
+    pub fn identity() -> Self {
+        Self::new(1.0, 0.0)
+    }
+
     pub fn scale(&self) -> f64 {
         self.scale
     }
@@ -117,21 +318,262 @@ impl ConversionFactor {
     pub fn offset(&self) -> f64 {
         self.offset
     }
+
+    /// Apply the factor to a value: `scale * value + offset`.
+    pub fn apply(&self, value: f64) -> f64 {
+        value * self.scale + self.offset
+    }
+
+    /// The factor going the opposite way: `x = (y - offset) / scale`.
+    pub fn inverse(&self) -> Self {
+        Self::new(1.0 / self.scale, -self.offset / self.scale)
+    }
+
+    /// Compose two factors: the result applies `self` first, then `next`.
+    /// `a2 * (a1 * x + b1) + b2 = (a2 * a1) * x + (a2 * b1 + b2)`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use conversion_wiz::ConversionFactor;
+    ///
+    /// let c_to_k = ConversionFactor::new(1.0, 273.15);
+    /// let k_to_f = ConversionFactor::new(1.8, -459.67);
+    /// let c_to_f = c_to_k.then(&k_to_f);
+    /// assert!(c_to_f.approx_eq(&ConversionFactor::new(1.8, 32.0), 1e-9));
+    /// ```
+    pub fn then(&self, next: &ConversionFactor) -> Self {
+        Self::new(next.scale * self.scale, next.scale * self.offset + next.offset)
+    }
+
+    /// Compare two factors allowing for floating-point noise.
+    /// Each component may differ by `tolerance`, relative to its magnitude when that is above 1.
+    pub fn approx_eq(&self, other: &ConversionFactor, tolerance: f64) -> bool {
+        let close = |a: f64, b: f64| (a - b).abs() <= tolerance * a.abs().max(b.abs()).max(1.0);
+        close(self.scale, other.scale) && close(self.offset, other.offset)
+    }
+
+    /// Whether the factor is the identity, within `tolerance`.
+    pub fn is_identity(&self, tolerance: f64) -> bool {
+        self.approx_eq(&Self::identity(), tolerance)
+    }
+}
+
+impl Default for ConversionFactor {
+    fn default() -> Self {
+        Self::identity()
+    }
+}
+
+/// Displays the factor as its formula, e.g. `x * 1.8 + 32`.
+impl fmt::Display for ConversionFactor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.offset < 0.0 {
+            write!(f, "x * {} - {}", self.scale, -self.offset)
+        } else {
+            // adding 0.0 turns -0 into 0
+            write!(f, "x * {} + {}", self.scale, self.offset + 0.0)
+        }
+    }
+}
+
+/// `ConversionStep` struct to represent one hop of a conversion, as returned by
+/// `ConversionGraph::explain`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConversionStep {
+    from: String,
+    to: String,
+    factor: ConversionFactor,
+    input: f64,
+    output: f64,
+    intermediate: bool,
+}
+
+impl ConversionStep {
+    /// Canonical name of the unit the hop starts from.
+    pub fn from(&self) -> &str {
+        &self.from
+    }
+
+    /// Canonical name of the unit the hop ends at.
+    pub fn to(&self) -> &str {
+        &self.to
+    }
+
+    /// The factor applied on this hop.
+    pub fn factor(&self) -> ConversionFactor {
+        self.factor
+    }
+
+    /// The value before the hop, in the `from` unit.
+    pub fn input(&self) -> f64 {
+        self.input
+    }
+
+    /// The value after the hop, in the `to` unit.
+    pub fn output(&self) -> f64 {
+        self.output
+    }
+
+    /// Whether the `to` unit is an intermediate unit, normally hidden from the user.
+    pub fn intermediate(&self) -> bool {
+        self.intermediate
+    }
+}
+
+/// An edge of the `ConversionGraph`: the factor to apply and whether
+/// the conversion is exact by definition (e.g. 1 inch = 0.0254 meter).
+struct Edge {
+    factor: ConversionFactor,
+    /// the exact factor, kept in rational mode
+    rational: Option<RationalFactor>,
+    exact: bool,
+    /// whether the edge goes the way the conversion was added; the reverse edge
+    /// holds the inverse factor and is `false`
+    declared: bool,
+}
+
+/// `TieBreaker` decides between several shortest paths (same number of hops)
+/// connecting two units. Remaining ties are broken by unit name so the chosen
+/// path never depends on insertion or hash order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TieBreaker {
+    /// Prefer the path whose unit names sort first.
+    #[default]
+    Lexicographic,
+    /// Prefer the path with the fewest edges not marked exact.
+    PreferExact,
+}
+
+impl TieBreaker {
+    /// cost added by an edge on top of the hop count
+    fn penalty(&self, edge: &Edge) -> usize {
+        match self {
+            TieBreaker::Lexicographic => 0,
+            TieBreaker::PreferExact => usize::from(!edge.exact),
+        }
+    }
+}
+
+/// `DuplicateEdgePolicy` decides what happens when a conversion is added between two
+/// units that already have one (in either direction).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DuplicateEdgePolicy {
+    /// Reject the new conversion with `ConversionError::DuplicateEdge`.
+    Error,
+    /// Replace the old conversion and record a warning (see `ConversionGraph::warnings`).
+    #[default]
+    Warn,
+    /// Replace the old conversion silently.
+    Replace,
+    /// Compose the two conversions: the old one is applied first, then the new one.
+    /// This joins a scale and an offset given as separate entries into one affine conversion.
+    Merge,
+}
+
+/// A unit alias resolved to the canonical name of its unit, possibly with a prefix.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Resolved<'a> {
+    name: &'a str,
+    prefix: Option<&'static Prefix>,
+}
+
+impl<'a> Resolved<'a> {
+    /// factor from the (possibly prefixed) unit to its base unit
+    fn prefix_factor(&self) -> ConversionFactor {
+        match self.prefix {
+            Some(prefix) => ConversionFactor::new(prefix.factor(), 0.0),
+            None => ConversionFactor::identity(),
+        }
+    }
+
+    /// same as `prefix_factor`, exact
+    fn prefix_rational(&self) -> RationalFactor {
+        RationalFactor::from_factor(&self.prefix_factor())
+    }
+
+    /// canonical name with the prefix name in front, e.g. "kilometer"
+    fn display_name(&self) -> Cow<'a, str> {
+        match self.prefix {
+            Some(prefix) => Cow::Owned(format!("{}{}", prefix.name(), self.name)),
+            None => Cow::Borrowed(self.name),
+        }
+    }
+}
+
+/// One hop of a conversion, before values are applied.
+struct Hop<'a> {
+    from: Cow<'a, str>,
+    to: Cow<'a, str>,
+    factor: ConversionFactor,
+    /// the exact factor, in rational mode
+    rational: Option<RationalFactor>,
+    intermediate: bool,
+}
+
+/// Result of `ConversionGraph::search`: the parent of every reached unit (the source
+/// has none) and the units in the order they were settled, source first.
+struct SearchTree<'a> {
+    parents: HashMap<&'a str, &'a str>,
+    order: Vec<&'a str>,
+}
+
+impl<'a> SearchTree<'a> {
+    /// The factor converting a value in the root of the tree to each reached unit,
+    /// composed along the tree.
+    fn factors_from_root(&self, graph: &ConversionGraph) -> HashMap<&'a str, ConversionFactor> {
+        self.compose(graph, ConversionFactor::identity(), |factor, edge| factor.then(&edge.factor))
+    }
+
+    /// Compose factors along the tree, starting with `root` at the root of the tree;
+    /// `then` extends the factor of a unit by the edge to its child.
+    fn compose<F>(&self, graph: &ConversionGraph, root: F, then: impl Fn(&F, &Edge) -> F) -> HashMap<&'a str, F> {
+        let mut factors: HashMap<&'a str, F> = HashMap::with_capacity(self.order.len());
+        let mut root = Some(root);
+        // units are settled after their parents, so a parent's factor is always known
+        for &unit in &self.order {
+            let factor = match self.parents.get(unit) {
+                Some(&parent) => then(&factors[parent], &graph.edges[parent][unit]),
+                None => root.take().expect("a search tree has one root"),
+            };
+            factors.insert(unit, factor);
+        }
+        factors
+    }
 }
 
 /// `ConversionGraph` struct to represent the entire conversion system.
 /// It includes a collection of units and the edges that represent conversion rates between units.
-/// The conversion rates are stored in a nested `HashMap` where the key is the target unit
+/// The conversion rates are stored in a nested `BTreeMap` where the key is the target unit
 /// and the value is the conversion factor to go from the outer unit to the inner unit.
 pub struct ConversionGraph {
     /// A map of unit names to `Unit` structs, allowing quick access to unit details.
     name_to_units: HashMap<String, Unit>,
     /// a map of aliases to unit names
     aliases_to_name: HashMap<String, String>,
-    /// A nested map where each unit name maps to another `HashMap`.
-    /// This inner `HashMap` represents the conversion rates to other units.
+    /// A nested map where each unit name maps to another `BTreeMap`.
+    /// This inner `BTreeMap` represents the conversion rates to other units.
     /// For example, edges["meter"]["kilometer"] might be 0.001.
-    edges: HashMap<String, HashMap<String, ConversionFactor>>,
+    /// Ordered maps keep traversal, and therefore path selection, deterministic.
+    edges: BTreeMap<String, BTreeMap<String, Edge>>,
+    /// how to choose between paths with the same number of hops
+    tie_breaker: TieBreaker,
+    /// what to do when a conversion between two connected units is added again
+    duplicate_edge_policy: DuplicateEdgePolicy,
+    /// problems that did not stop an operation, e.g. replaced conversions
+    warnings: Vec<ConversionError>,
+    /// whether edges keep exact rational factors and conversions compose them
+    rational_mode: bool,
+    /// how aliases that match no unit exactly are looked up
+    unit_lookup: UnitLookup,
+    /// the aliases as lenient lookups compare them, cleared whenever an alias changes
+    alias_table: OnceLock<AliasTable>,
+}
+
+impl Default for ConversionGraph {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 /// `ConversionGraph` implementation
@@ -141,22 +583,93 @@ impl ConversionGraph {
     /// # Examples
     ///
     /// ```
+    /// use conversion_wiz::ConversionGraph;
+    ///
     /// let graph = ConversionGraph::new();
     /// ```
     pub fn new() -> Self {
         Self {
             name_to_units: HashMap::new(),
             aliases_to_name: HashMap::new(),
-            edges: HashMap::new(),
+            edges: BTreeMap::new(),
+            tie_breaker: TieBreaker::default(),
+            duplicate_edge_policy: DuplicateEdgePolicy::default(),
+            warnings: Vec::new(),
+            rational_mode: false,
+            unit_lookup: UnitLookup::default(),
+            alias_table: OnceLock::new(),
         }
     }
 
+    /// Set how `convert` chooses between several shortest paths.
+    pub fn set_tie_breaker(&mut self, tie_breaker: TieBreaker) {
+        self.tie_breaker = tie_breaker;
+    }
+
+    pub fn tie_breaker(&self) -> TieBreaker {
+        self.tie_breaker
+    }
+
+    /// Set what adding a conversion between two units that already have one does.
+    pub fn set_duplicate_edge_policy(&mut self, policy: DuplicateEdgePolicy) {
+        self.duplicate_edge_policy = policy;
+    }
+
+    pub fn duplicate_edge_policy(&self) -> DuplicateEdgePolicy {
+        self.duplicate_edge_policy
+    }
+
+    /// Problems recorded without failing an operation, such as conversions replaced
+    /// under `DuplicateEdgePolicy::Warn`, oldest first.
+    pub fn warnings(&self) -> &[ConversionError] {
+        &self.warnings
+    }
+
+    /// Remove and return the recorded warnings.
+    pub fn take_warnings(&mut self) -> Vec<ConversionError> {
+        std::mem::take(&mut self.warnings)
+    }
+
+    /// Turn rational mode on or off. In rational mode every conversion also keeps an exact
+    /// `RationalFactor`, paths are composed exactly, and `convert` and `factor_between`
+    /// only round to `f64` at the end. Float factors given to `add_edge` are taken as
+    /// the shortest decimal that rounds to them (`0.3048` is 3048/10000); config factors
+    /// can also be written as strings such as `"1200/3937"`.
+    ///
+    /// Conversions already in the graph get exact factors read from their floats, so turn
+    /// the mode on before adding conversions to keep the exact ones of a config.
+    pub fn set_rational_mode(&mut self, on: bool) {
+        self.rational_mode = on;
+        if !on {
+            self.edges.values_mut().flat_map(|edges| edges.values_mut()).for_each(|edge| edge.rational = None);
+            return;
+        }
+        let mut reverse = Vec::new();
+        for (from, edges) in &mut self.edges {
+            for (to, edge) in edges.iter_mut().filter(|(_, edge)| edge.declared && edge.rational.is_none()) {
+                let rational = RationalFactor::from_factor(&edge.factor);
+                reverse.push((to.clone(), from.clone(), rational.inverse()));
+                edge.rational = Some(rational);
+            }
+        }
+        for (from, to, rational) in reverse {
+            if let Some(edge) = self.edges.get_mut(&from).and_then(|edges| edges.get_mut(&to)) {
+                edge.rational = Some(rational);
+            }
+        }
+    }
+
+    pub fn rational_mode(&self) -> bool {
+        self.rational_mode
+    }
+
     /// Adds a new unit to the `ConversionGraph`.
     ///
     /// # Arguments
     ///
     /// * `name` - The canonical name of the unit.
     /// * `aliases` - A vector of aliases (alternative names) for the unit.
+    /// * `intermediate` - Whether the unit is hidden from the user.
     ///
     /// # Errors
     ///
@@ -166,70 +679,188 @@ impl ConversionGraph {
     /// # Examples
     ///
     /// ```
-    /// graph.add_unit("Meter", vec!["m", "metre"]).expect("Failed to add unit");
+    /// use conversion_wiz::ConversionGraph;
+    ///
+    /// let mut graph = ConversionGraph::new();
+    /// graph.add_unit("Meter", vec!["m", "metre"], false).expect("Failed to add unit");
     /// ```
     pub fn add_unit(&mut self, name: &str, aliases: Vec<&str>, intermediate: bool) -> Result<(), ConversionError> {
-        if name.is_empty() {
-            return Err(ConversionError::EmptyUnitName);
-        }
-        if self.name_to_units.contains_key(name) {
-            return Err(ConversionError::DuplicateUnit(name.to_string()));
-        }
-
         let unit = Unit::new(name, aliases, intermediate)?;
+        self.insert_unit(unit)
+    }
+
+    /// Same as `add_unit`, but SI prefixes can be applied to the unit (e.g. `km` for "meter").
+    /// Use `Unit::with_prefixes` and `insert_unit` for binary prefixes.
+    pub fn add_prefixable_unit(&mut self, name: &str, aliases: Vec<&str>, intermediate: bool) -> Result<(), ConversionError> {
+        let unit = Unit::new(name, aliases, intermediate)?.with_prefixes(PrefixKind::Si);
+        self.insert_unit(unit)
+    }
+
+    /// Adds an already built `Unit` to the `ConversionGraph`, e.g. one with a dimension.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ConversionError::DuplicateUnit` if the unit name already exists in the graph.
+    /// Returns `ConversionError::DuplicateAlias` if any alias already exists in the graph.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use conversion_wiz::{ConversionGraph, Unit};
+    ///
+    /// let mut graph = ConversionGraph::new();
+    /// let meter = Unit::new("meter", vec!["m"], false).unwrap()
+    ///     .with_dimension("length".parse().unwrap());
+    /// graph.insert_unit(meter).expect("Failed to add unit");
+    /// ```
+    pub fn insert_unit(&mut self, unit: Unit) -> Result<(), ConversionError> {
+        if self.name_to_units.contains_key(&unit.name) {
+            return Err(ConversionError::DuplicateUnit(unit.name.clone()));
+        }
+        // check every alias before inserting any, so a failed insert leaves the graph untouched
         for alias in unit.aliases() {
             if self.aliases_to_name.contains_key(alias) {
                 return Err(ConversionError::DuplicateAlias(alias.to_string()));
             }
-            self.aliases_to_name.insert(alias.to_string(), name.to_string());
         }
-        self.name_to_units.entry(name.to_string()).or_insert(unit);
+        for alias in unit.aliases() {
+            self.aliases_to_name.insert(alias.to_string(), unit.name.clone());
+        }
+        self.alias_table.take();
+        self.name_to_units.insert(unit.name.clone(), unit);
         Ok(())
     }
 
+    /// Get a unit by name or alias. Prefixed aliases are not resolved.
+    pub fn unit(&self, name: &str) -> Option<&Unit> {
+        self.aliases_to_name.get(name).and_then(|name| self.name_to_units.get(name))
+    }
+
+    /// Whether the name is an alias of a unit, or a prefix applied to a prefixable unit.
     pub fn contains_unit(&self, name: &str) -> bool {
-        // see if name is in aliases_to_name
-        self.aliases_to_name.contains_key(name) 
+        self.resolve(name).is_ok()
     }
     /// Adds a new conversion rate between two units.
+    /// The conversion is the affine map `to = scale * from + offset`; the reverse
+    /// edge `from = (to - offset) / scale` is added automatically.
     ///
     /// # Arguments
     ///
     /// * `from` - The unit name to convert from.
     /// * `to` - The unit name to convert to.
     /// * `scale` - The scale factor for the conversion.
-    /// * `offset` - The offset for the conversion.
+    /// * `offset` - The offset for the conversion, added after scaling.
     ///
     /// # Errors
     ///
     /// Returns `ConversionError::UnitNotFound` if either unit is not found in the graph.
     /// Returns `ConversionError::ConversionRateZero` if the conversion rate is zero.
+    /// Returns `ConversionError::InvalidConversion` if the scale or offset is not finite,
+    /// or both units are the same unit (such as `km` and `m`).
+    /// Returns `ConversionError::DimensionMismatch` if both units declare different dimensions.
+    /// Returns `ConversionError::DuplicateEdge` if the units already have a conversion and
+    /// the `DuplicateEdgePolicy` is `Error`.
     ///
     /// # Examples
     ///
     /// ```
+    /// use conversion_wiz::ConversionGraph;
+    ///
+    /// let mut graph = ConversionGraph::new();
+    /// graph.add_unit("Meter", vec!["m"], false).unwrap();
+    /// graph.add_unit("Kilometer", vec!["km"], false).unwrap();
+    /// graph.add_unit("Celsius", vec!["C"], false).unwrap();
+    /// graph.add_unit("Fahrenheit", vec!["F"], false).unwrap();
     /// graph.add_edge("Meter", "Kilometer", 0.001, 0.0).expect("Failed to add conversion");
+    /// graph.add_edge("Celsius", "Fahrenheit", 1.8, 32.0).expect("Failed to add conversion");
     /// ```
     pub fn add_edge(&mut self, from: &str, to: &str, scale: f64, offset: f64) -> Result<(), ConversionError> {
-        if scale == 0.0 {
+        self.insert_edge(from, to, scale, offset, false)
+    }
+
+    /// Same as `add_edge`, but marks the conversion as exact by definition.
+    /// Exact edges are preferred when the tie breaker is `TieBreaker::PreferExact`.
+    pub fn add_exact_edge(&mut self, from: &str, to: &str, scale: f64, offset: f64) -> Result<(), ConversionError> {
+        self.insert_edge(from, to, scale, offset, true)
+    }
+
+    fn insert_edge(&mut self, from: &str, to: &str, scale: f64, offset: f64, exact: bool) -> Result<(), ConversionError> {
+        let factor = ConversionFactor::new(scale, offset);
+        let rational = (self.rational_mode && factor.scale().is_finite() && factor.offset().is_finite())
+            .then(|| RationalFactor::from_factor(&factor));
+        self.insert_factor_edge(from, to, factor, rational, exact)
+    }
+
+    /// Add a conversion given by its float factor and, in rational mode, its exact factor.
+    fn insert_factor_edge(
+        &mut self,
+        from: &str,
+        to: &str,
+        factor: ConversionFactor,
+        rational: Option<RationalFactor>,
+        exact: bool,
+    ) -> Result<(), ConversionError> {
+        if factor.scale() == 0.0 || rational.as_ref().is_some_and(|rational| rational.scale().is_zero()) {
             return Err(ConversionError::ConversionRateZero);
         }
-        if scale != 1.0 && offset != 0.0 {
-            return Err(ConversionError::ConversionRateBothValues);
-        }
-        let from_name = self.aliases_to_name.get(from)
-            .ok_or_else(|| ConversionError::UnitNotFound(from.to_string()))?;
-        let to_name = self.aliases_to_name.get(to)
-            .ok_or_else(|| ConversionError::UnitNotFound(to.to_string()))?;
-
-        self.edges.entry(from_name.to_string()).or_insert_with(HashMap::new);
-        self.edges.entry(to_name.to_string()).or_insert_with(HashMap::new);
-        
-        let conversion = ConversionFactor::new(scale, offset);
-        let opposite_conversion = ConversionFactor::new(1.0 / scale, -offset);
-        
-        self.edges.get_mut(from_name).unwrap().insert(to_name.to_string(), conversion);
-        self.edges.get_mut(to_name).unwrap().insert(from_name.to_string(), opposite_conversion);
+        let invalid = |reason: String| ConversionError::InvalidConversion(from.to_string(), to.to_string(), reason);
+        if !factor.scale().is_finite() {
+            return Err(invalid(format!("scale {} is not finite", factor.scale())));
+        }
+        if !factor.offset().is_finite() {
+            return Err(invalid(format!("offset {} is not finite", factor.offset())));
+        }
+        let from_unit = self.resolve(from)?;
+        let to_unit = self.resolve(to)?;
+        if from_unit.name == to_unit.name {
+            return Err(invalid(format!("both are the unit {}", from_unit.name)));
+        }
+        self.check_dimensions(from_unit.name, to_unit.name)?;
+
+        // edges always connect base units, so fold any prefixes into the factor
+        let mut conversion = from_unit.prefix_factor().inverse()
+            .then(&factor)
+            .then(&to_unit.prefix_factor());
+        let mut rational = rational.map(|rational| {
+            from_unit.prefix_rational().inverse()
+                .then(&rational)
+                .then(&to_unit.prefix_rational())
+        });
+        let mut exact = exact;
+        let from_name = from_unit.name.to_string();
+        let to_name = to_unit.name.to_string();
+
+        // the reverse edge is always stored too, so one lookup covers both directions
+        if let Some(existing) = self.edges.get(&from_name).and_then(|edges| edges.get(&to_name)) {
+            let duplicate = || ConversionError::DuplicateEdge(from_name.clone(), to_name.clone());
+            match self.duplicate_edge_policy {
+                DuplicateEdgePolicy::Error => return Err(duplicate()),
+                DuplicateEdgePolicy::Warn => self.warnings.push(duplicate()),
+                DuplicateEdgePolicy::Replace => {}
+                DuplicateEdgePolicy::Merge => {
+                    conversion = existing.factor.then(&conversion);
+                    rational = rational.map(|rational| match &existing.rational {
+                        Some(existing) => existing.then(&rational),
+                        None => RationalFactor::from_factor(&existing.factor).then(&rational),
+                    });
+                    exact &= existing.exact;
+                }
+            }
+        }
+        if let Some(rational) = &rational {
+            // stay as close as possible to the exact factor
+            conversion = rational.to_factor();
+        }
+        let opposite_conversion = match &rational {
+            Some(rational) => rational.inverse().to_factor(),
+            None => conversion.inverse(),
+        };
+        let opposite_rational = rational.as_ref().map(RationalFactor::inverse);
+
+        self.edges.entry(from_name.clone()).or_default()
+            .insert(to_name.clone(), Edge { factor: conversion, rational, exact, declared: true });
+        self.edges.entry(to_name).or_default()
+            .insert(from_name, Edge { factor: opposite_conversion, rational: opposite_rational, exact, declared: false });
         Ok(())
     }
     
@@ -241,86 +872,288 @@ impl ConversionGraph {
         self.add_edge(from, to, 1.0, offset)
     }
 
-    /// Get the conversion rate from one unit to another.
+    /// Get the composed conversion factor from one unit to another.
+    /// The returned factor can be cached and applied to any number of values.
     ///
     /// # Error
     ///
-    /// Error if either of the units do not exist in the graph.
-    pub fn convert(&self, from: &str, to: &str, value: f64) -> Result<f64, ConversionError> {
-        let from_name = self.aliases_to_name.get(from)
-            .ok_or_else(|| ConversionError::UnitNotFound(from.to_string()))?;
-        let to_name = self.aliases_to_name.get(to)
-            .ok_or_else(|| ConversionError::UnitNotFound(to.to_string()))?;
+    /// Error if either of the units do not exist in the graph or no path connects them.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use conversion_wiz::ConversionGraph;
+    ///
+    /// let mut graph = ConversionGraph::new();
+    /// graph.add_unit("Celsius", vec!["C"], false).unwrap();
+    /// graph.add_unit("Fahrenheit", vec!["F"], false).unwrap();
+    /// graph.add_edge("C", "F", 1.8, 32.0).unwrap();
+    /// let factor = graph.factor_between("C", "F").unwrap();
+    /// assert_eq!(factor.apply(100.0), 212.0);
+    /// ```
+    pub fn factor_between(&self, from: &str, to: &str) -> Result<ConversionFactor, ConversionError> {
+        if self.rational_mode {
+            return Ok(self.rational_factor_between(from, to)?.to_factor());
+        }
+        let hops = self.hops(from, to)?;
+
+        // fold the path into a single affine map
+        let total = hops.iter().fold(ConversionFactor::identity(), |total, hop| total.then(&hop.factor));
+        Ok(total)
+    }
+
+    /// Get the units visited when converting from one unit to another, including hidden
+    /// intermediate units. The path is the one `convert` uses: fewest hops, then the
+    /// configured `TieBreaker`, then unit name order. Units are given by canonical name;
+    /// a prefixed unit appears as the prefix name followed by its base unit name
+    /// (e.g. "kilometer"), next to the base unit itself.
+    ///
+    /// # Error
+    ///
+    /// Error if either of the units do not exist in the graph or no path connects them.
+    pub fn path(&self, from: &str, to: &str) -> Result<Vec<String>, ConversionError> {
+        let hops = self.hops(from, to)?;
+        match hops.first() {
+            Some(first) => {
+                let mut path = vec![first.from.to_string()];
+                path.extend(hops.iter().map(|hop| hop.to.to_string()));
+                Ok(path)
+            }
+            None => Ok(vec![self.resolve(from)?.display_name().into_owned()]),
+        }
+    }
+
+    /// Look up both aliases, check their dimensions agree and list the hops between them,
+    /// including the implicit hops from a prefixed unit to its base unit and back.
+    fn hops(&self, from: &str, to: &str) -> Result<Vec<Hop<'_>>, ConversionError> {
+        let from_unit = self.resolve(from)?;
+        let to_unit = self.resolve(to)?;
+        self.check_dimensions(from_unit.name, to_unit.name)?;
+        if from_unit == to_unit {
+            return Ok(Vec::new()); // No conversion needed if units are the same.
+        }
+
+        let path = self.find_path(from_unit.name, to_unit.name)
+            .ok_or_else(|| ConversionError::ConversionPathNotFound(from.to_string(), to.to_string()))?;
+
+        let mut hops = Vec::with_capacity(path.len() + 1);
+        if from_unit.prefix.is_some() {
+            hops.push(Hop {
+                from: from_unit.display_name(),
+                to: Cow::Borrowed(from_unit.name),
+                factor: from_unit.prefix_factor(),
+                rational: self.rational_mode.then(|| from_unit.prefix_rational()),
+                intermediate: false,
+            });
+        }
+        for pair in path.windows(2) {
+            let edge = self.edges.get(pair[0])
+                .and_then(|edges| edges.get(pair[1]))
+                .ok_or(ConversionError::MissingConversionFactor)?;
+            hops.push(Hop {
+                from: Cow::Borrowed(pair[0]),
+                to: Cow::Borrowed(pair[1]),
+                factor: edge.factor,
+                rational: edge.rational.clone(),
+                intermediate: self.name_to_units.get(pair[1]).is_some_and(|unit| unit.intermediate),
+            });
+        }
+        if to_unit.prefix.is_some() {
+            hops.push(Hop {
+                from: Cow::Borrowed(to_unit.name),
+                to: to_unit.display_name(),
+                factor: to_unit.prefix_factor().inverse(),
+                rational: self.rational_mode.then(|| to_unit.prefix_rational().inverse()),
+                intermediate: false,
+            });
+        }
+        Ok(hops)
+    }
+
+    /// Resolve an alias to its unit, either directly or as a prefix applied to a
+    /// prefixable unit (e.g. "km" to kilo + "meter"). Failing that, the alias is looked
+    /// up as the `UnitLookup` of the graph allows.
+    fn resolve(&self, alias: &str) -> Result<Resolved<'_>, ConversionError> {
+        if let Some(name) = self.aliases_to_name.get(alias) {
+            return Ok(Resolved { name, prefix: None });
+        }
+        let accepts = |rest: &str, kind: PrefixKind| self.unit(rest).is_some_and(|unit| unit.accepts_prefix(kind));
+        if let Some((prefix, rest)) = split_prefix(alias, accepts) {
+            return Ok(Resolved { name: &self.aliases_to_name[rest], prefix: Some(prefix) });
+        }
+        let accepts = |name: &str, kind: PrefixKind| self.name_to_units[name].accepts_prefix(kind);
+        match self.unit_lookup.matches(alias, self.alias_table(), accepts)[..] {
+            [(name, prefix, _)] => Ok(Resolved { name, prefix }),
+            _ => Err(ConversionError::unit_not_found(alias)),
+        }
+    }
+
+    /// Fail if both units declare a dimension and the dimensions differ.
+    fn check_dimensions(&self, from_name: &str, to_name: &str) -> Result<(), ConversionError> {
+        let from_dimension = self.name_to_units.get(from_name).and_then(Unit::dimension);
+        let to_dimension = self.name_to_units.get(to_name).and_then(Unit::dimension);
+        match (from_dimension, to_dimension) {
+            (Some(from_dimension), Some(to_dimension)) if from_dimension != to_dimension => {
+                Err(ConversionError::DimensionMismatch {
+                    from: from_name.to_string(),
+                    from_dimension,
+                    to: to_name.to_string(),
+                    to_dimension,
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Explain a conversion hop by hop: the units walked (including hidden intermediate
+    /// units), the factor applied on each hop and the value after each hop.
+    /// Converting a unit to itself gives no steps.
+    ///
+    /// # Error
+    ///
+    /// Error if either of the units do not exist in the graph or no path connects them.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use conversion_wiz::ConversionGraph;
+    ///
+    /// let mut graph = ConversionGraph::new();
+    /// graph.add_unit("Celsius", vec!["C"], false).unwrap();
+    /// graph.add_unit("Kelvin", vec!["K"], false).unwrap();
+    /// graph.add_edge("C", "K", 1.0, 273.15).unwrap();
+    /// let steps = graph.explain("C", "K", 0.0).unwrap();
+    /// assert_eq!(steps.len(), 1);
+    /// assert_eq!(steps[0].to(), "Kelvin");
+    /// assert_eq!(steps[0].output(), 273.15);
+    /// ```
+    pub fn explain(&self, from: &str, to: &str, value: f64) -> Result<Vec<ConversionStep>, ConversionError> {
+        let hops = self.hops(from, to)?;
+        let mut steps = Vec::with_capacity(hops.len());
+        let mut cur_value = value;
+        for hop in hops {
+            let output = hop.factor.apply(cur_value);
+            steps.push(ConversionStep {
+                from: hop.from.into_owned(),
+                to: hop.to.into_owned(),
+                factor: hop.factor,
+                input: cur_value,
+                output,
+                intermediate: hop.intermediate,
+            });
+            cur_value = output;
+        }
+        Ok(steps)
+    }
 
-        if from_name == to_name {
-            return Ok(value); // No conversion needed if units are the same.
+    /// Find the path between two canonical unit names.
+    fn find_path<'a>(&'a self, from_name: &'a str, to_name: &'a str) -> Option<Vec<&'a str>> {
+        let tree = self.search(from_name, Some(to_name));
+        if !tree.order.contains(&to_name) {
+            return None;
         }
 
-        let mut queue = std::collections::VecDeque::new();
-        let mut visited = HashMap::new();
-        let mut parents = HashMap::new();
+        let mut path = vec![to_name];
+        let mut current_unit = to_name;
+        while let Some(&parent_unit) = tree.parents.get(current_unit) {
+            path.push(parent_unit);
+            current_unit = parent_unit;
+        }
+        path.reverse();
+        Some(path)
+    }
 
-        // Initialize the BFS
-        queue.push_back(from_name.as_str());
-        visited.insert(from_name.as_str(), true);
+    /// Single-source shortest path search from a canonical unit name, stopping early once
+    /// `to_name` is settled (or exploring the whole component when it is `None`).
+    /// Runs Dijkstra over the cost (hops, tie breaker penalty); the heap is keyed by unit
+    /// name as well, so equal-cost candidates are always settled in the same order.
+    fn search<'a>(&'a self, from_name: &'a str, to_name: Option<&str>) -> SearchTree<'a> {
+        let mut best: HashMap<&str, (usize, usize)> = HashMap::new();
+        let mut tree = SearchTree { parents: HashMap::new(), order: Vec::new() };
+        let mut heap = BinaryHeap::new();
 
-        // Perform the BFS
-        while let Some(current_unit) = queue.pop_front() {
-            if current_unit == to_name.as_str() {
-                // Found a path to the target unit.
+        best.insert(from_name, (0, 0));
+        heap.push(Reverse((0, 0, from_name)));
+
+        while let Some(Reverse((hops, penalty, current_unit))) = heap.pop() {
+            if best.get(current_unit) != Some(&(hops, penalty)) {
+                continue; // stale heap entry
+            }
+            tree.order.push(current_unit);
+            if Some(current_unit) == to_name {
                 break;
             }
 
             // Visit all adjacent units (i.e., conversions)
             if let Some(edges) = self.edges.get(current_unit) {
-                // print edges
-            
-                for (adj_unit, _) in edges {
-                    if !visited.contains_key(adj_unit.as_str()) {
-                        queue.push_back(adj_unit);
-                        visited.insert(adj_unit, true);
-                        parents.insert(adj_unit, current_unit);
+                for (adj_unit, edge) in edges {
+                    let cost = (hops + 1, penalty + self.tie_breaker.penalty(edge));
+                    if best.get(adj_unit.as_str()).is_none_or(|&known| cost < known) {
+                        best.insert(adj_unit, cost);
+                        tree.parents.insert(adj_unit, current_unit);
+                        heap.push(Reverse((cost.0, cost.1, adj_unit.as_str())));
                     }
                 }
             }
         }
+        tree
+    }
 
-        if !parents.contains_key(&to_name.to_string()) {
-            return Err(ConversionError::ConversionPathNotFound(from.to_string(), to.to_string()));
-        }
+    /// A search tree spanning every connected component, rooted at the unit of the component
+    /// whose name sorts first. Trees are in root name order.
+    fn spanning_forest(&self) -> Vec<SearchTree<'_>> {
+        let mut names: Vec<&String> = self.name_to_units.keys().collect();
+        names.sort();
 
-        
-        let mut cur_value = value;
-        let mut current_unit = to_name.to_string();
-        // need to add unit to vector to reverse
-        let mut stack = Vec::new();
-        while let Some(&parent_unit) = parents.get(&current_unit) {
-            let factor = self.edges.get(parent_unit)
-                .and_then(|edges| edges.get(&current_unit))
-                .ok_or(ConversionError::MissingConversionFactor)?;
-            stack.push(factor);
-            current_unit = parent_unit.to_string();
-        }
-        while let Some(factor) = stack.pop() {
-            cur_value = cur_value * factor.scale() + factor.offset();
+        let mut reached: HashSet<&str> = HashSet::with_capacity(names.len());
+        let mut forest = Vec::new();
+        for root in names {
+            // units are visited in name order, so the first unit seen in a component is its root
+            if reached.contains(root.as_str()) {
+                continue;
+            }
+            let tree = self.search(root, None);
+            reached.extend(tree.order.iter().copied());
+            forest.push(tree);
         }
-
-        Ok(cur_value)
+        forest
     }
 
+    /// Compile the graph into an immutable `CompiledGraph` where every conversion is a
+    /// constant-time lookup. See `CompiledGraph` for details.
+    pub fn compile(&self) -> CompiledGraph {
+        CompiledGraph::from_graph(self)
+    }
 
-    /// get a list of all units formatted as strings
-    pub fn units_formatted(&self) -> Vec<String> {
-        // self.name_to_units.values().map(|u| u.format_string()).collect()
-        //grab units from self.name_to_units if !intermediate
-        let mut units = Vec::new();
-        for unit in self.name_to_units.values() {
-            if !unit.intermediate {
-                units.push(unit.format_string());
-            }
+    /// Get the conversion rate from one unit to another.
+    ///
+    /// # Error
+    ///
+    /// Error if either of the units do not exist in the graph.
+    pub fn convert(&self, from: &str, to: &str, value: f64) -> Result<f64, ConversionError> {
+        if self.rational_mode && value.is_finite() {
+            let value = self.convert_rational(from, to, &rational_from_f64(value))?;
+            return Ok(rational_to_f64(&value));
         }
+        let factor = self.factor_between(from, to)?;
+        Ok(factor.apply(value))
+    }
+
+
+    /// Get every unit of the graph, including intermediate units, sorted by name.
+    pub fn units(&self) -> Vec<&Unit> {
+        let mut units: Vec<&Unit> = self.name_to_units.values().collect();
+        units.sort_by(|a, b| a.name.cmp(&b.name));
         units
     }
+
+    /// get a list of all visible units formatted as strings, sorted by name
+    pub fn units_formatted(&self) -> Vec<String> {
+        self.units().into_iter()
+            .filter(|unit| !unit.intermediate)
+            .map(Unit::format_string)
+            .collect()
+    }
 }
 
 #[cfg(test)]
@@ -332,63 +1165,87 @@ mod tests {
 
     #[test]
     fn test_unit_new_valid() {
-        let u = Unit::new("Kelvin", vec!["K"]).expect("Failed to create unit");
+        let u = Unit::new("Kelvin", vec!["K"], false).expect("Failed to create unit");
         assert_eq!(u.name(), "Kelvin");
         assert_eq!(u.aliases().len(), 2); // Includes the name itself as an alias
     }
 
     #[test]
     fn test_unit_new_empty_name() {
-        assert!(matches!(Unit::new("", vec!["K"]), Err(ConversionError::EmptyUnitName)));
+        assert!(matches!(Unit::new("", vec!["K"], false), Err(ConversionError::EmptyUnitName)));
     }
 
     #[test]
     fn test_unit_new_empty_alias() {
-        assert!(matches!(Unit::new("Kelvin", vec![""]), Err(ConversionError::EmptyAlias)));
+        assert!(matches!(Unit::new("Kelvin", vec![""], false), Err(ConversionError::EmptyAlias)));
     }
 
     #[test]
     fn test_conversion_graph_add_unit_valid() {
         let mut graph = ConversionGraph::new();
-        assert!(graph.add_unit("Kelvin", vec!["K"]).is_ok());
+        assert!(graph.add_unit("Kelvin", vec!["K"], false).is_ok());
     }
 
     #[test]
     fn test_conversion_graph_add_duplicate_unit() {
         let mut graph = ConversionGraph::new();
-        let _ = graph.add_unit("Kelvin", vec!["K"]);
-        assert!(graph.add_unit("Kelvin", vec!["K"]).is_err());
+        let _ = graph.add_unit("Kelvin", vec!["K"], false);
+        assert!(graph.add_unit("Kelvin", vec!["K"], false).is_err());
     }
 
     #[test]
     fn test_conversion_graph_add_duplicate_alias() {
         let mut graph = ConversionGraph::new();
-        let _ = graph.add_unit("Kelvin", vec!["K"]);
-        assert!(graph.add_unit("Rankine", vec!["K"]).is_err());
+        let _ = graph.add_unit("Kelvin", vec!["K"], false);
+        assert!(graph.add_unit("Rankine", vec!["K"], false).is_err());
     }
 
     #[test]
     fn test_conversion_graph_add_edge_valid() {
         let mut graph = ConversionGraph::new();
-        let _ = graph.add_unit("Kelvin", vec!["K"]);
-        let _ = graph.add_unit("Rankine", vec!["R"]);
+        let _ = graph.add_unit("Kelvin", vec!["K"], false);
+        let _ = graph.add_unit("Rankine", vec!["R"], false);
         assert!(graph.add_edge("K", "R", 1.8, 0.0).is_ok());
     }
 
     #[test]
     fn test_conversion_graph_add_edge_zero_rate() {
         let mut graph = ConversionGraph::new();
-        let _ = graph.add_unit("Kelvin", vec!["K"]);
-        let _ = graph.add_unit("Rankine", vec!["R"]);
+        let _ = graph.add_unit("Kelvin", vec!["K"], false);
+        let _ = graph.add_unit("Rankine", vec!["R"], false);
         assert!(graph.add_edge("K", "R", 0.0, 0.0).is_err());
     }
 
+    #[test]
+    fn test_conversion_graph_add_edge_non_finite() {
+        let mut graph = ConversionGraph::new();
+        let _ = graph.add_unit("Kelvin", vec!["K"], false);
+        let _ = graph.add_unit("Rankine", vec!["R"], false);
+        for (scale, offset) in [(f64::NAN, 0.0), (f64::INFINITY, 0.0), (1.8, f64::NEG_INFINITY), (1.8, f64::NAN)] {
+            let result = graph.add_edge("K", "R", scale, offset);
+            assert!(matches!(result, Err(ConversionError::InvalidConversion(..))), "{} {}", scale, offset);
+        }
+        assert!(graph.to_config().conversions.is_empty());
+    }
+
+    #[test]
+    fn test_conversion_graph_add_edge_self_loop() {
+        let mut graph = ConversionGraph::new();
+        let _ = graph.add_prefixable_unit("meter", vec!["m"], false);
+        for (from, to) in [("km", "m"), ("m", "meter"), ("mm", "km")] {
+            let result = graph.add_edge(from, to, 1000.0, 0.0);
+            assert!(matches!(result, Err(ConversionError::InvalidConversion(..))), "{} -> {}", from, to);
+        }
+        assert!(graph.edges.is_empty());
+        assert_eq!(graph.convert("km", "m", 1.0).unwrap(), 1000.0);
+    }
+
 
     #[test]
     fn test_conversion_graph_convert_valid() {
         let mut graph = ConversionGraph::new();
-        let _ = graph.add_unit("Kelvin", vec!["K"]);
-        let _ = graph.add_unit("Rankine", vec!["R"]);
+        let _ = graph.add_unit("Kelvin", vec!["K"], false);
+        let _ = graph.add_unit("Rankine", vec!["R"], false);
         let _ = graph.add_edge("K", "R", 1.8, 0.0);
         let converted_value = graph.convert("K", "R", 100.0).expect("Conversion should be successful");
         assert_relative_eq!(converted_value, 180.0, max_relative = REL_TOL); // Check only scale as offset is zero
@@ -398,8 +1255,8 @@ mod tests {
     #[test]
     fn test_conversion_with_offset() {
         let mut graph = ConversionGraph::new();
-        let _ = graph.add_unit("Celsius", vec!["C"]);
-        let _ = graph.add_unit("Kelvin", vec!["K"]);
+        let _ = graph.add_unit("Celsius", vec!["C"], false);
+        let _ = graph.add_unit("Kelvin", vec!["K"], false);
         let _ = graph.add_edge("C", "K", 1.0, 273.15);
         let converted_value = graph.convert("C", "K", 15.0).expect("Conversion should be successful");
         assert_relative_eq!(converted_value, 288.15, max_relative = REL_TOL);
@@ -408,18 +1265,234 @@ mod tests {
     }
 
 
+    #[test]
+    fn test_conversion_affine_edge() {
+        let mut graph = ConversionGraph::new();
+        let _ = graph.add_unit("Celsius", vec!["C"], false);
+        let _ = graph.add_unit("Fahrenheit", vec!["F"], false);
+        assert!(graph.add_edge("C", "F", 1.8, 32.0).is_ok());
+        let converted_value = graph.convert("C", "F", 100.0).expect("Conversion should be successful");
+        assert_relative_eq!(converted_value, 212.0, max_relative = REL_TOL);
+        // inverse is (F - 32) / 1.8
+        let converted_value_reverse = graph.convert("F", "C", 98.6).expect("Conversion should be successful");
+        assert_relative_eq!(converted_value_reverse, 37.0, max_relative = REL_TOL);
+    }
+
+    #[test]
+    fn test_conversion_affine_path_composition() {
+        let mut graph = ConversionGraph::new();
+        let _ = graph.add_unit("Kelvin", vec!["K"], false);
+        let _ = graph.add_unit("Celsius", vec!["C"], false);
+        let _ = graph.add_unit("Fahrenheit", vec!["F"], false);
+        let _ = graph.add_edge("K", "C", 1.0, -273.15);
+        let _ = graph.add_edge("C", "F", 1.8, 32.0);
+        let converted_value = graph.convert("K", "F", 0.0).expect("Conversion should be successful");
+        assert_relative_eq!(converted_value, -459.67, max_relative = REL_TOL);
+        let converted_value_reverse = graph.convert("F", "K", 32.0).expect("Conversion should be successful");
+        assert_relative_eq!(converted_value_reverse, 273.15, max_relative = REL_TOL);
+    }
+
+    #[test]
+    fn test_conversion_factor_algebra() {
+        let f = ConversionFactor::new(1.8, 32.0);
+        assert!(f.then(&f.inverse()).is_identity(REL_TOL));
+        assert!(f.inverse().then(&f).is_identity(REL_TOL));
+        assert_eq!(ConversionFactor::identity().then(&f), f);
+        assert_eq!(f.then(&ConversionFactor::identity()), f);
+        // composition is associative
+        let g = ConversionFactor::new(0.5, -3.0);
+        let h = ConversionFactor::new(4.0, 1.0);
+        assert!(f.then(&g).then(&h).approx_eq(&f.then(&g.then(&h)), REL_TOL));
+        assert!(!f.approx_eq(&g, REL_TOL));
+    }
+
+    #[test]
+    fn test_conversion_graph_factor_between() {
+        let mut graph = ConversionGraph::new();
+        let _ = graph.add_unit("Kelvin", vec!["K"], false);
+        let _ = graph.add_unit("Celsius", vec!["C"], false);
+        let _ = graph.add_unit("Fahrenheit", vec!["F"], false);
+        let _ = graph.add_edge("C", "K", 1.0, 273.15);
+        let _ = graph.add_edge("C", "F", 1.8, 32.0);
+        let factor = graph.factor_between("K", "F").expect("Path should exist");
+        assert!(factor.approx_eq(&ConversionFactor::new(1.8, -459.67), REL_TOL));
+        assert_eq!(graph.factor_between("K", "K").unwrap(), ConversionFactor::identity());
+        assert!(matches!(graph.factor_between("K", "X"), Err(ConversionError::UnitNotFound { .. })));
+    }
+
+    /// Diamond graph A - {B, C} - D where both routes have two hops but
+    /// disagree slightly; only the A - C - D route is exact.
+    fn diamond_graph(insert_reversed: bool) -> ConversionGraph {
+        let mut graph = ConversionGraph::new();
+        for name in ["A", "B", "C", "D"] {
+            let _ = graph.add_unit(name, vec![], false);
+        }
+        let mut edges = vec![("A", "B", 2.0, false), ("B", "D", 3.0, false), ("A", "C", 3.0, true), ("C", "D", 2.000001, true)];
+        if insert_reversed {
+            edges.reverse();
+        }
+        for (from, to, scale, exact) in edges {
+            if exact {
+                let _ = graph.add_exact_edge(from, to, scale, 0.0);
+            } else {
+                let _ = graph.add_edge(from, to, scale, 0.0);
+            }
+        }
+        graph
+    }
+
+    #[test]
+    fn test_conversion_graph_deterministic_path() {
+        for insert_reversed in [false, true] {
+            let graph = diamond_graph(insert_reversed);
+            assert_eq!(graph.path("A", "D").unwrap(), vec!["A", "B", "D"]);
+            assert_eq!(graph.convert("A", "D", 1.0).unwrap(), 6.0);
+            assert_eq!(graph.path("D", "A").unwrap(), vec!["D", "B", "A"]);
+        }
+    }
+
+    #[test]
+    fn test_conversion_graph_prefer_exact_path() {
+        for insert_reversed in [false, true] {
+            let mut graph = diamond_graph(insert_reversed);
+            graph.set_tie_breaker(TieBreaker::PreferExact);
+            assert_eq!(graph.path("A", "D").unwrap(), vec!["A", "C", "D"]);
+            assert_relative_eq!(graph.convert("A", "D", 1.0).unwrap(), 6.000003, max_relative = REL_TOL);
+        }
+    }
+
+    #[test]
+    fn test_conversion_graph_fewest_hops_wins() {
+        let mut graph = diamond_graph(false);
+        graph.set_tie_breaker(TieBreaker::PreferExact);
+        let _ = graph.add_edge("A", "D", 6.5, 0.0);
+        assert_eq!(graph.path("A", "D").unwrap(), vec!["A", "D"]);
+    }
+
+    #[test]
+    fn test_conversion_graph_explain() {
+        let mut graph = ConversionGraph::new();
+        let _ = graph.add_unit("Celsius", vec!["C"], false);
+        let _ = graph.add_unit("c1", vec![], true);
+        let _ = graph.add_unit("Fahrenheit", vec!["F"], false);
+        let _ = graph.add_edge("C", "c1", 1.8, 0.0);
+        let _ = graph.add_edge("c1", "F", 1.0, 32.0);
+
+        let steps = graph.explain("C", "F", 100.0).expect("Path should exist");
+        assert_eq!(steps.len(), 2);
+        assert_eq!((steps[0].from(), steps[0].to()), ("Celsius", "c1"));
+        assert!(steps[0].intermediate());
+        assert_relative_eq!(steps[0].output(), 180.0, max_relative = REL_TOL);
+        assert_eq!(steps[1].factor(), ConversionFactor::new(1.0, 32.0));
+        assert_relative_eq!(steps[1].input(), 180.0, max_relative = REL_TOL);
+        assert_relative_eq!(steps[1].output(), 212.0, max_relative = REL_TOL);
+        assert!(!steps[1].intermediate());
+        assert!(graph.explain("F", "F", 1.0).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_conversion_graph_dimension_mismatch_edge() {
+        let mut graph = ConversionGraph::new();
+        let meter = Unit::new("meter", vec!["m"], false).unwrap().with_dimension("length".parse().unwrap());
+        let celsius = Unit::new("Celsius", vec!["C"], false).unwrap().with_dimension("temperature".parse().unwrap());
+        let _ = graph.insert_unit(meter);
+        let _ = graph.insert_unit(celsius);
+        let _ = graph.add_unit("thing", vec![], false);
+        assert!(matches!(graph.add_edge("m", "C", 1.0, 0.0), Err(ConversionError::DimensionMismatch { .. })));
+        // units without a dimension are not checked
+        assert!(graph.add_edge("m", "thing", 2.0, 0.0).is_ok());
+    }
+
+    #[test]
+    fn test_conversion_graph_dimension_mismatch_convert() {
+        let mut graph = ConversionGraph::new();
+        let meter = Unit::new("meter", vec!["m"], false).unwrap().with_dimension("length".parse().unwrap());
+        let celsius = Unit::new("Celsius", vec!["C"], false).unwrap().with_dimension("temperature".parse().unwrap());
+        let _ = graph.insert_unit(meter);
+        let _ = graph.insert_unit(celsius);
+        let _ = graph.add_unit("bridge", vec![], true);
+        let _ = graph.add_edge("m", "bridge", 1.0, 0.0);
+        let _ = graph.add_edge("bridge", "C", 1.0, 0.0);
+        let result = graph.convert("m", "C", 1.0);
+        assert!(matches!(result, Err(ConversionError::DimensionMismatch { .. })));
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "Cannot convert between 'meter' (length) and 'Celsius' (temperature): dimensions differ"
+        );
+    }
+
+    #[test]
+    fn test_conversion_graph_insert_unit_duplicate_alias_is_atomic() {
+        let mut graph = ConversionGraph::new();
+        let _ = graph.add_unit("Kelvin", vec!["K"], false);
+        assert!(graph.add_unit("Rankine", vec!["R", "K"], false).is_err());
+        assert!(!graph.contains_unit("R"));
+    }
+
+    fn prefixed_graph() -> ConversionGraph {
+        let mut graph = ConversionGraph::new();
+        let meter = Unit::new("meter", vec!["m"], false).unwrap().with_prefixes(PrefixKind::Si);
+        let byte = Unit::new("byte", vec!["B"], false).unwrap()
+            .with_prefixes(PrefixKind::Si)
+            .with_prefixes(PrefixKind::Binary);
+        let _ = graph.insert_unit(meter);
+        let _ = graph.insert_unit(byte);
+        let _ = graph.add_unit("bit", vec!["b"], false);
+        let _ = graph.add_unit("mile", vec!["mi"], false);
+        let _ = graph.add_edge("B", "b", 8.0, 0.0);
+        let _ = graph.add_edge("km", "mi", 1.0 / 1.609344, 0.0);
+        graph
+    }
+
+    #[test]
+    fn test_conversion_graph_si_prefixes() {
+        let graph = prefixed_graph();
+        assert!(graph.contains_unit("km") && graph.contains_unit("µm") && graph.contains_unit("kilometer"));
+        assert!(!graph.contains_unit("kmi"));
+        // a prefix name goes with a unit name, a prefix symbol with a unit symbol
+        assert!(!graph.contains_unit("kilom") && !graph.contains_unit("kmeter"));
+        assert_relative_eq!(graph.convert("km", "m", 1.5).unwrap(), 1500.0, max_relative = REL_TOL);
+        assert_relative_eq!(graph.convert("mm", "µm", 1.0).unwrap(), 1000.0, max_relative = REL_TOL);
+        assert_relative_eq!(graph.convert("Gm", "kilometer", 1.0).unwrap(), 1e6, max_relative = REL_TOL);
+        // the edge was declared on "km" and folded onto "meter"
+        assert_relative_eq!(graph.convert("mi", "m", 1.0).unwrap(), 1609.344, max_relative = REL_TOL);
+        assert_eq!(graph.path("km", "mi").unwrap(), vec!["kilometer", "meter", "mile"]);
+    }
+
+    #[test]
+    fn test_conversion_graph_binary_prefixes() {
+        let graph = prefixed_graph();
+        assert_relative_eq!(graph.convert("KiB", "B", 1.0).unwrap(), 1024.0, max_relative = REL_TOL);
+        assert_relative_eq!(graph.convert("MiB", "kB", 1.0).unwrap(), 1048.576, max_relative = REL_TOL);
+        assert_relative_eq!(graph.convert("kB", "b", 1.0).unwrap(), 8000.0, max_relative = REL_TOL);
+        // bit is not prefixable, mile takes no binary prefixes
+        assert!(matches!(graph.convert("kb", "b", 1.0), Err(ConversionError::UnitNotFound { .. })));
+        assert!(!graph.contains_unit("Kim"));
+    }
+
+    #[test]
+    fn test_conversion_graph_explain_prefixes() {
+        let graph = prefixed_graph();
+        let steps = graph.explain("km", "cm", 2.0).unwrap();
+        assert_eq!(steps.len(), 2);
+        assert_eq!((steps[0].from(), steps[0].to()), ("kilometer", "meter"));
+        assert_relative_eq!(steps[0].output(), 2000.0, max_relative = REL_TOL);
+        assert_eq!(steps[1].to(), "centimeter");
+        assert_relative_eq!(steps[1].output(), 200000.0, max_relative = REL_TOL);
+    }
+
     #[test]
     fn test_conversion_graph_non_direct_route() {
         let mut graph = ConversionGraph::new();
-        let _ = graph.add_unit("A", vec!["a"]);
-        let _ = graph.add_unit("B", vec!["b"]);
-        let _ = graph.add_unit("C", vec!["c"]);
+        let _ = graph.add_unit("A", vec!["a"], false);
+        let _ = graph.add_unit("B", vec!["b"], false);
+        let _ = graph.add_unit("C", vec!["c"], false);
         let _ = graph.add_edge("A", "B", 2.0, 0.0);
         let _ = graph.add_edge("B", "C", 1.0, 3.0);
 
-        /// Test conversion from A to C which requires a conversion from A to B, then B to C.
+        // Test conversion from A to C which requires a conversion from A to B, then B to C.
         let converted_value = graph.convert("A", "C", 1.0).expect("Conversion should be successful");
-        assert_relative_eq!(converted_value, 5.0, max_relative = REL_TOL); /// 1 A = 2 B, 1 B = 3 C, thus 1 A = 6 C
+        assert_relative_eq!(converted_value, 5.0, max_relative = REL_TOL); // 1 A = 2 B, 1 B = 3 C, thus 1 A = 6 C
         let converted_value_reverse = graph.convert("C", "A", 5.0).expect("Conversion should be successful");
         assert_relative_eq!(converted_value_reverse, 1.0, max_relative = REL_TOL);
     }
@@ -427,11 +1500,65 @@ mod tests {
     #[test]
     fn test_conversion_graph_nonexistent_route() {
         let mut graph = ConversionGraph::new();
-        let _ = graph.add_unit("A", vec!["a"]);
-        let _ = graph.add_unit("C", vec!["c"]);
+        let _ = graph.add_unit("A", vec!["a"], false);
+        let _ = graph.add_unit("C", vec!["c"], false);
 
-        /// No direct conversion edge between A and C
+        // No direct conversion edge between A and C
         let conversion_result = graph.convert("A", "C", 0.0);
         assert!(matches!(conversion_result, Err(ConversionError::ConversionPathNotFound(_, _))));
     }
+
+    #[test]
+    fn test_conversion_graph_units_sorted() {
+        let mut graph = ConversionGraph::new();
+        let _ = graph.add_unit("meter", vec!["m"], false);
+        let _ = graph.add_unit("Kelvin", vec!["K"], false);
+        let _ = graph.add_unit("k1", vec![], true);
+        let names: Vec<&str> = graph.units().iter().map(|u| u.name()).collect();
+        assert_eq!(names, vec!["Kelvin", "k1", "meter"]);
+    }
+
+    fn duplicate_edge_graph(policy: DuplicateEdgePolicy) -> ConversionGraph {
+        let mut graph = ConversionGraph::new();
+        graph.set_duplicate_edge_policy(policy);
+        let _ = graph.add_unit("Kelvin", vec!["K"], false);
+        let _ = graph.add_unit("Celsius", vec!["C"], false);
+        let _ = graph.add_scale_edge("K", "C", 1.0);
+        graph
+    }
+
+    #[test]
+    fn test_duplicate_edge_error() {
+        let mut graph = duplicate_edge_graph(DuplicateEdgePolicy::Error);
+        let result = graph.add_offset_edge("C", "K", 273.15);
+        assert!(matches!(result, Err(ConversionError::DuplicateEdge(from, to)) if from == "Celsius" && to == "Kelvin"));
+        assert_relative_eq!(graph.convert("K", "C", 300.0).unwrap(), 300.0, max_relative = REL_TOL);
+    }
+
+    #[test]
+    fn test_duplicate_edge_warn_and_replace() {
+        let mut graph = duplicate_edge_graph(DuplicateEdgePolicy::Warn);
+        graph.add_offset_edge("K", "C", -273.15).unwrap();
+        assert_relative_eq!(graph.convert("C", "K", 0.0).unwrap(), 273.15, max_relative = REL_TOL);
+        assert!(matches!(graph.warnings(), [ConversionError::DuplicateEdge(_, _)]));
+        assert_eq!(graph.take_warnings().len(), 1);
+        assert!(graph.warnings().is_empty());
+
+        let mut graph = duplicate_edge_graph(DuplicateEdgePolicy::Replace);
+        graph.add_offset_edge("K", "C", -273.15).unwrap();
+        assert_relative_eq!(graph.convert("K", "C", 273.15).unwrap(), 0.0, epsilon = REL_TOL);
+        assert!(graph.warnings().is_empty());
+    }
+
+    #[test]
+    fn test_duplicate_edge_merge() {
+        let mut graph = duplicate_edge_graph(DuplicateEdgePolicy::Merge);
+        let _ = graph.add_unit("Fahrenheit", vec!["F"], false);
+        graph.add_scale_edge("C", "F", 1.8).unwrap();
+        graph.add_offset_edge("C", "F", 32.0).unwrap();
+        assert_relative_eq!(graph.convert("C", "F", 100.0).unwrap(), 212.0, max_relative = REL_TOL);
+        // the second entry may be given in the opposite direction
+        graph.add_offset_edge("C", "K", 273.15).unwrap();
+        assert_relative_eq!(graph.convert("K", "C", 373.15).unwrap(), 100.0, max_relative = REL_TOL);
+    }
 }
@@ -0,0 +1,339 @@
+//! Lenient unit lookup: case folding, Unicode normalization and suggestions for
+//! unknown units.
+
+use std::collections::HashMap;
+
+use unicode_normalization::UnicodeNormalization;
+
+use crate::prefix::{Prefix, PREFIXES};
+use crate::{ConversionError, ConversionGraph, PrefixKind};
+
+/// Most suggestions given for an unknown unit.
+const MAX_SUGGESTIONS: usize = 3;
+
+/// `UnitLookup` struct to describe how an alias that matches no unit exactly is looked up.
+///
+/// With Unicode normalization (NFKC), spellings that only differ in compatibility
+/// characters match: `℃` is `°C`, the micro sign `µ` is the Greek `μ` and `m²` is `m2`.
+/// With case folding, `KM` is `km` and `celsius` is `Celsius`. An exact match always
+/// wins, and a lenient one is only used if it points to a single unit, so `MM` (which
+/// could be `mm` or `Mm`) is still not found.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnitLookup {
+    case_insensitive: bool,
+    normalize_unicode: bool,
+}
+
+impl Default for UnitLookup {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl UnitLookup {
+    /// Case sensitive, with Unicode normalization.
+    pub fn new() -> Self {
+        Self { case_insensitive: false, normalize_unicode: true }
+    }
+
+    /// Exact matches only.
+    pub const fn exact() -> Self {
+        Self { case_insensitive: false, normalize_unicode: false }
+    }
+
+    pub fn with_case_folding(mut self, on: bool) -> Self {
+        self.case_insensitive = on;
+        self
+    }
+
+    pub fn with_unicode_normalization(mut self, on: bool) -> Self {
+        self.normalize_unicode = on;
+        self
+    }
+
+    pub fn case_insensitive(&self) -> bool {
+        self.case_insensitive
+    }
+
+    pub fn normalizes_unicode(&self) -> bool {
+        self.normalize_unicode
+    }
+
+    /// The form aliases are compared in.
+    pub fn key(&self, alias: &str) -> String {
+        let key: String = match self.normalize_unicode {
+            true => alias.nfkc().collect(),
+            false => alias.to_string(),
+        };
+        match self.case_insensitive {
+            true => key.to_lowercase(),
+            false => key,
+        }
+    }
+
+    /// The form of an alias of the table that `key` would give.
+    fn table_key<'a>(&self, keys: &'a AliasKeys) -> &'a str {
+        match (self.normalize_unicode, self.case_insensitive) {
+            (true, true) => &keys.normalized_folded,
+            (true, false) => &keys.normalized,
+            (false, true) => &keys.folded,
+            (false, false) => &keys.alias,
+        }
+    }
+
+    /// Every unit `alias` matches once compared by `key`, directly or with a prefix in
+    /// front of an alias of a unit that `accepts` it, without repeats. Each comes with
+    /// the exact spelling that matched, e.g. `km` for `KM`.
+    pub(crate) fn matches<'a>(
+        &self,
+        alias: &str,
+        table: &'a AliasTable,
+        accepts: impl Fn(&str, PrefixKind) -> bool,
+    ) -> Vec<(&'a str, Option<&'static Prefix>, String)> {
+        if !self.case_insensitive && !self.normalize_unicode {
+            return Vec::new();
+        }
+        let wanted = self.key(alias);
+        let mut found: Vec<(&'a str, Option<&'static Prefix>, String)> = Vec::new();
+        for keys in &table.aliases {
+            let (candidate, name) = (&keys.alias, &keys.name);
+            let key = self.table_key(keys);
+            let mut push = |prefix: Option<&'static Prefix>, spelling: String| {
+                if !found.iter().any(|(n, p, _)| n == name && p.map(Prefix::name) == prefix.map(Prefix::name)) {
+                    found.push((name.as_str(), prefix, spelling));
+                }
+            };
+            if key == wanted {
+                push(None, candidate.clone());
+                continue;
+            }
+            let Some(start) = wanted.strip_suffix(key).filter(|start| !start.is_empty()) else {
+                continue;
+            };
+            for prefix in PREFIXES.iter().filter(|prefix| accepts(name, prefix.kind())) {
+                let found = prefix.spellings().find(|spelling| self.key(spelling) == start && prefix.combines(spelling, candidate));
+                if let Some(spelling) = found {
+                    push(Some(prefix), format!("{}{}", spelling, candidate));
+                }
+            }
+        }
+        found
+    }
+}
+
+/// An alias with the forms it is compared in.
+#[derive(Debug, Clone)]
+struct AliasKeys {
+    alias: String,
+    name: String,
+    normalized: String,
+    folded: String,
+    normalized_folded: String,
+}
+
+/// `AliasTable` struct to hold the aliases of a graph in every form `UnitLookup` compares
+/// them in, so lenient lookups do not normalize each alias again. Sorted by alias, so the
+/// spelling found for a unit does not depend on hash order.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct AliasTable {
+    aliases: Vec<AliasKeys>,
+}
+
+impl AliasTable {
+    pub(crate) fn new(aliases_to_name: &HashMap<String, String>) -> Self {
+        let mut aliases: Vec<AliasKeys> = aliases_to_name
+            .iter()
+            .map(|(alias, name)| {
+                let normalized: String = alias.nfkc().collect();
+                AliasKeys {
+                    alias: alias.clone(),
+                    name: name.clone(),
+                    folded: alias.to_lowercase(),
+                    normalized_folded: normalized.to_lowercase(),
+                    normalized,
+                }
+            })
+            .collect();
+        aliases.sort_by(|a, b| a.alias.cmp(&b.alias));
+        Self { aliases }
+    }
+}
+
+/// Spellings `alias` matches with case folding, then aliases close to it by edit
+/// distance, ignoring case and compatibility characters, best first. Only the closest
+/// alias of each unit is given.
+pub(crate) fn suggestions(
+    alias: &str,
+    table: &AliasTable,
+    visible: impl Fn(&str) -> bool,
+    accepts: impl Fn(&str, PrefixKind) -> bool,
+) -> Vec<String> {
+    let loose = UnitLookup::new().with_case_folding(true);
+    let mut found: Vec<String> = loose.matches(alias, table, accepts)
+        .into_iter()
+        .filter(|(name, _, _)| visible(name))
+        .map(|(_, _, spelling)| spelling)
+        .collect();
+    let wanted = loose.key(alias);
+    // allow about one typo in every three characters
+    let limit = (wanted.chars().count() / 3).max(1);
+    let mut best: HashMap<&str, (usize, &str)> = HashMap::new();
+    for keys in table.aliases.iter().filter(|keys| visible(&keys.name)) {
+        let (candidate, name) = (keys.alias.as_str(), keys.name.as_str());
+        let distance = strsim::osa_distance(&wanted, loose.table_key(keys));
+        if distance > limit {
+            continue;
+        }
+        let entry = best.entry(name).or_insert((distance, candidate));
+        if (distance, candidate) < *entry {
+            *entry = (distance, candidate);
+        }
+    }
+    let mut ranked: Vec<(usize, &str)> = best.into_values().collect();
+    ranked.sort();
+    for (_, candidate) in ranked {
+        if !found.iter().any(|spelling| spelling == candidate) {
+            found.push(candidate.to_string());
+        }
+    }
+    found.truncate(MAX_SUGGESTIONS);
+    found
+}
+
+/// Unit lookup.
+impl ConversionGraph {
+    /// Set how aliases that match no unit exactly are looked up.
+    pub fn set_unit_lookup(&mut self, lookup: UnitLookup) {
+        self.unit_lookup = lookup;
+    }
+
+    pub fn unit_lookup(&self) -> UnitLookup {
+        self.unit_lookup
+    }
+
+    /// Aliases of visible units that look like `alias`, e.g. `Celsius` for `Celcius`,
+    /// closest first.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use conversion_wiz::ConversionGraph;
+    ///
+    /// let mut graph = ConversionGraph::new();
+    /// graph.add_unit("Celsius", vec!["C", "degC"], false).unwrap();
+    /// graph.add_unit("Fahrenheit", vec!["F", "degF"], false).unwrap();
+    /// assert_eq!(graph.suggestions("Celcius"), vec!["Celsius"]);
+    /// assert_eq!(graph.suggestions("degK"), vec!["degC", "degF"]);
+    /// ```
+    pub fn suggestions(&self, alias: &str) -> Vec<String> {
+        let visible = |name: &str| !self.name_to_units[name].intermediate();
+        let accepts = |name: &str, kind: PrefixKind| self.name_to_units[name].accepts_prefix(kind);
+        suggestions(alias, self.alias_table(), visible, accepts)
+    }
+
+    /// `error` with `suggestions` filled in if it is a `ConversionError::UnitNotFound`.
+    /// Lookups leave them out, as finding them compares the unit with every alias of the
+    /// graph; add them where the error is shown.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use conversion_wiz::ConversionGraph;
+    ///
+    /// let mut graph = ConversionGraph::new();
+    /// graph.add_unit("kelvin", vec!["K"], false).unwrap();
+    /// let error = graph.convert("kelvn", "K", 1.0).unwrap_err();
+    /// assert_eq!(graph.with_suggestions(error).to_string(), "Cannot find unit kelvn, did you mean kelvin?");
+    /// ```
+    pub fn with_suggestions(&self, error: ConversionError) -> ConversionError {
+        match error {
+            ConversionError::UnitNotFound { unit, suggestions } if suggestions.is_empty() => {
+                let suggestions = self.suggestions(&unit);
+                ConversionError::UnitNotFound { unit, suggestions }
+            }
+            error => error,
+        }
+    }
+
+    /// The aliases in the forms lenient lookups compare them in, built on first use.
+    pub(crate) fn alias_table(&self) -> &AliasTable {
+        self.alias_table.get_or_init(|| AliasTable::new(&self.aliases_to_name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_graph(lookup: UnitLookup) -> ConversionGraph {
+        let mut graph = ConversionGraph::new();
+        graph.set_unit_lookup(lookup);
+        graph.add_prefixable_unit("meter", vec!["m"], false).unwrap();
+        graph.add_unit("Celsius", vec!["°C", "degC"], false).unwrap();
+        graph.add_unit("kelvin", vec!["K"], false).unwrap();
+        graph.add_edge("degC", "K", 1.0, 273.15).unwrap();
+        graph
+    }
+
+    #[test]
+    fn test_unicode_normalization() {
+        let graph = sample_graph(UnitLookup::new());
+        assert_eq!(graph.convert("℃", "K", 0.0).unwrap(), 273.15);
+        // the Kelvin sign
+        assert_eq!(graph.convert("\u{212a}", "degC", 0.0).unwrap(), -273.15);
+        assert_eq!(graph.convert("µm", "m", 1e6).unwrap(), 1.0);
+        assert!(graph.convert("celsius", "K", 0.0).is_err());
+        assert!(sample_graph(UnitLookup::exact()).convert("℃", "K", 0.0).is_err());
+    }
+
+    #[test]
+    fn test_case_folding() {
+        let graph = sample_graph(UnitLookup::new().with_case_folding(true));
+        assert_eq!(graph.convert("celsius", "k", 0.0).unwrap(), 273.15);
+        assert_eq!(graph.convert("KM", "M", 1.0).unwrap(), 1000.0);
+        assert_eq!(graph.convert("Kilometer", "m", 1.0).unwrap(), 1000.0);
+        assert!(graph.convert("KILOM", "m", 1.0).is_err());
+        assert!(graph.convert("KMETER", "m", 1.0).is_err());
+        // exact matches win: "Mm" is a megameter even if "mm" folds to the same key
+        assert_eq!(graph.convert("Mm", "km", 1.0).unwrap(), 1000.0);
+        // "MM" could be a millimeter or a megameter
+        assert!(matches!(graph.convert("MM", "m", 1.0), Err(ConversionError::UnitNotFound { .. })));
+    }
+
+    #[test]
+    fn test_lookup_follows_edits() {
+        let mut graph = sample_graph(UnitLookup::new().with_case_folding(true));
+        assert_eq!(graph.convert("CELSIUS", "K", 0.0).unwrap(), 273.15);
+        graph.rename_unit("Celsius", "centigrade").unwrap();
+        assert!(graph.convert("CELSIUS", "K", 0.0).is_err());
+        assert_eq!(graph.convert("CENTIGRADE", "K", 0.0).unwrap(), 273.15);
+        graph.remove_alias("degC").unwrap();
+        assert!(graph.convert("DEGC", "K", 0.0).is_err());
+        graph.add_unit("Rankine", vec!["R"], false).unwrap();
+        assert_eq!(graph.suggestions("rankin"), vec!["Rankine"]);
+    }
+
+    #[test]
+    fn test_suggestions() {
+        let graph = sample_graph(UnitLookup::new());
+        // lookups leave the suggestions out
+        let error = graph.convert("Celcius", "K", 0.0).unwrap_err();
+        assert!(matches!(&error, ConversionError::UnitNotFound { suggestions, .. } if suggestions.is_empty()));
+        match graph.with_suggestions(error) {
+            ConversionError::UnitNotFound { unit, suggestions } => {
+                assert_eq!(unit, "Celcius");
+                assert_eq!(suggestions, vec!["Celsius"]);
+            }
+            other => panic!("expected UnitNotFound, got {:?}", other),
+        }
+        let not_found = |alias: &str| graph.with_suggestions(ConversionError::unit_not_found(alias)).to_string();
+        assert_eq!(not_found("kelvn"), "Cannot find unit kelvn, did you mean kelvin?");
+        assert_eq!(not_found("furlong"), "Cannot find unit furlong");
+        // one alias per unit, closest first
+        assert_eq!(graph.suggestions("degc"), vec!["degC"]);
+        assert_eq!(graph.suggestions("mn"), vec!["m"]);
+        // spellings that only differ in case come first
+        assert_eq!(graph.suggestions("KM"), vec!["km", "K", "m"]);
+        assert_eq!(graph.suggestions("MM"), vec!["Mm", "mm", "m"]);
+    }
+}
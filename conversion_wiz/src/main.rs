@@ -1,143 +1,799 @@
-use clap::{App, Arg};
-use serde::{Deserialize, Serialize};
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::fmt;
 use std::fs;
-use std::io::{self};
-use conversion_wiz::ConversionGraph;
+use std::io::{self, Read};
+use std::process;
+use conversion_wiz::{
+    BatchError, BatchOptions, CatalogLoader, Config, ConfigFormat, ConfigValidator, ConversionError, ConversionGraph, ConversionStep, DuplicateEdgePolicy,
+    Notation, NumberFormat, Precision, Query, Severity, Unit, UnitLookup, significant_figures, MAX_DECIMAL_PLACES,
+};
 
-#[derive(Serialize, Deserialize)]
-struct UnitConfig {
-    name: String,
-    aliases: Vec<String>,
-    intermediate: bool,
+/// Output format of the non-interactive subcommands.
+#[derive(Clone, Copy, PartialEq)]
+enum OutputFormat {
+    Text,
+    Json,
 }
 
-#[derive(Serialize, Deserialize)]
-struct ConversionScale {
-    from: String,
-    to: String,
-    factor: f64,
+/// How results are written in text output.
+struct ResultFormat {
+    number: NumberFormat,
+    /// keep the significant figures of the input (`--sig-figs input`)
+    from_input: bool,
 }
 
-#[derive(Serialize, Deserialize)]
-struct ConversionOffset {
-    from: String,
-    to: String,
-    offset: f64,
+impl ResultFormat {
+    /// Write `value` in `unit`, the result of converting a value with `figures` significant figures.
+    fn write(&self, graph: &ConversionGraph, value: f64, unit: &str, figures: usize) -> String {
+        match self.from_input {
+            true => self.number.clone().with_precision(Precision::SignificantFigures(figures)).format(value),
+            false => graph.format_value(value, unit, &self.number),
+        }
+    }
 }
 
-#[derive(Serialize, Deserialize)]
-struct Config {
-    units: Vec<UnitConfig>,
-    conversions_scale: Vec<ConversionScale>,
-    conversions_offset: Vec<ConversionOffset>,
+/// Errors reported by the command line, each with its own exit code.
+enum CliError {
+    /// the config file could not be read or parsed
+    Config(String),
+    /// a command line argument could not be understood
+    InvalidArgument(String),
+    Conversion(ConversionError),
+    Batch(BatchError),
+    /// a batch ran to the end, but this many of its rows could not be converted
+    RowsFailed(usize, usize),
 }
 
-fn main() {
-    let matches = App::new("Temperature Converter")
-        .version("1.0")
-        .about("Converts between different temperature units")
-        .arg(
-            Arg::with_name("config")
-                .short("c")
-                .long("config")
-                .value_name("FILE")
-                .help("Sets a custom config file")
-                .takes_value(true),
-        )
-        .get_matches();
+impl CliError {
+    /// Exit code of the process, so scripts can tell failures apart without parsing messages.
+    fn exit_code(&self) -> i32 {
+        match self {
+            CliError::InvalidArgument(_) => 2,
+            CliError::Conversion(e) => match e {
+                ConversionError::InvalidNumber(_) => 2,
+                ConversionError::UnitNotFound { .. } => 3,
+                ConversionError::ConversionPathNotFound(_, _) => 4,
+                ConversionError::DimensionMismatch { .. } => 5,
+                ConversionError::InvalidUnitExpression(_) | ConversionError::OffsetUnitInCompound(_) => 6,
+                ConversionError::EmptyUnitName
+                | ConversionError::EmptyAlias
+                | ConversionError::DuplicateUnit(_)
+                | ConversionError::DuplicateAlias(_)
+                | ConversionError::ConversionRateZero
+                | ConversionError::MissingConversionFactor
+                | ConversionError::DuplicateEdge(_, _)
+                | ConversionError::InvalidConversion(_, _, _)
+                | ConversionError::EdgeNotFound(_, _)
+                | ConversionError::CannotRemoveUnitName(_)
+                | ConversionError::OrphanedIntermediateUnit(_)
+                | ConversionError::InvalidDimension(_) => 7,
+            },
+            CliError::Config(_) => 7,
+            CliError::Batch(BatchError::Csv(_)) => 8,
+            CliError::Batch(_) => 2,
+            CliError::RowsFailed(_, _) => 9,
+        }
+    }
+
+    /// Short machine-readable name of the error, used in JSON output.
+    fn kind(&self) -> &'static str {
+        match self {
+            CliError::Config(_) => "config",
+            CliError::InvalidArgument(_) => "invalid_argument",
+            CliError::Conversion(e) => match e {
+                ConversionError::InvalidNumber(_) => "invalid_argument",
+                ConversionError::UnitNotFound { .. } => "unit_not_found",
+                ConversionError::ConversionPathNotFound(_, _) => "conversion_path_not_found",
+                ConversionError::DimensionMismatch { .. } => "dimension_mismatch",
+                ConversionError::InvalidUnitExpression(_) => "invalid_unit_expression",
+                ConversionError::OffsetUnitInCompound(_) => "offset_unit_in_compound",
+                _ => "config",
+            },
+            CliError::Batch(BatchError::Csv(_)) => "batch_input",
+            CliError::Batch(_) => "invalid_argument",
+            CliError::RowsFailed(_, _) => "rows_failed",
+        }
+    }
+
+    /// Units the user may have meant, for an unknown unit.
+    fn suggestions(&self) -> &[String] {
+        match self {
+            CliError::Conversion(ConversionError::UnitNotFound { suggestions, .. }) => suggestions,
+            _ => &[],
+        }
+    }
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CliError::Config(message) | CliError::InvalidArgument(message) => write!(f, "{}", message),
+            CliError::Conversion(e) => write!(f, "{}", e),
+            CliError::Batch(e) => write!(f, "{}", e),
+            CliError::RowsFailed(failed, rows) => write!(f, "{} of {} rows could not be converted", failed, rows),
+        }
+    }
+}
+
+impl From<ConversionError> for CliError {
+    fn from(e: ConversionError) -> Self {
+        CliError::Conversion(e)
+    }
+}
 
-    let config_file = matches.value_of("data").unwrap_or("data.json");
+impl From<BatchError> for CliError {
+    fn from(e: BatchError) -> Self {
+        CliError::Batch(e)
+    }
+}
+
+/// Merge the config files, in order, with the files they include. With `standard`, the
+/// built-in catalog is the first layer.
+fn load_catalog(standard: bool, config_files: &[&str], config_format: Option<ConfigFormat>) -> Result<CatalogLoader, CliError> {
+    let mut catalog = CatalogLoader::new();
+    if standard {
+        catalog.add_config(Config::standard(), STANDARD_SOURCE).map_err(|e| CliError::Config(e.to_string()))?;
+    }
+    for config_file in config_files {
+        match config_format {
+            Some(format) => catalog.load_file_as(config_file, format),
+            None => catalog.load_file(config_file),
+        }
+        .map_err(|e| CliError::Config(e.to_string()))?;
+    }
+    Ok(catalog)
+}
 
-    let config_data = fs::read_to_string(config_file).expect("Unable to read file");
-    let config: Config = serde_json::from_str(&config_data).expect("JSON was not well-formatted");
+/// How the built-in catalog is named in messages and `list --sources`.
+const STANDARD_SOURCE: &str = "<standard>";
 
+/// Build the conversion graph described by the merged config files.
+/// Units given on the command line are looked up as `lookup` allows; the config is
+/// always loaded with exact lookup.
+fn load_graph(
+    catalog: &CatalogLoader,
+    config_files: &[&str],
+    policy: DuplicateEdgePolicy,
+    rational: bool,
+    lookup: UnitLookup,
+) -> Result<ConversionGraph, CliError> {
     let mut graph = ConversionGraph::new();
+    graph.set_duplicate_edge_policy(policy);
+    graph.set_rational_mode(rational);
+    graph.set_unit_lookup(lookup);
+    graph.load_config(catalog.config()).map_err(|e| {
+        CliError::Config(format!("Invalid config {}: {} (run the validate command for details)", config_files.join(", "), e))
+    })?;
+    Ok(graph)
+}
 
-    // Populate the graph with units
-    for unit in config.units {
-        graph
-            .add_unit(&unit.name, unit.aliases.iter().map(AsRef::as_ref).collect(), unit.intermediate)
-            .expect("Error adding unit");
+/// Print the warnings recorded while loading the graph to stderr.
+fn print_warnings(graph: &mut ConversionGraph, format: OutputFormat) {
+    for warning in graph.take_warnings() {
+        match format {
+            OutputFormat::Text => eprintln!("Warning: {}", warning),
+            OutputFormat::Json => eprintln!("{}", json!({ "warning": warning.to_string() })),
+        }
     }
+}
 
-    // Add scale conversions
-    for conv in config.conversions_scale {
-        graph
-            .add_scale_edge(&conv.from, &conv.to, conv.factor)
-            .expect("Error adding scale conversion");
+/// Check the merged config files and report every problem found, located in the file
+/// it comes from.
+fn run_validate(catalog: &CatalogLoader, config_files: &[&str], policy: DuplicateEdgePolicy, format: OutputFormat) -> Result<(), CliError> {
+    let validator = ConfigValidator::new().with_duplicate_edge_policy(policy);
+    let diagnostics = validator.validate(catalog.config());
+    let located: Vec<(String, String)> = diagnostics
+        .iter()
+        .map(|d| match catalog.locate(d.path()) {
+            Some((file, path)) => (file.display().to_string(), path),
+            None => (config_files.join(", "), d.path().to_string()),
+        })
+        .collect();
+    match format {
+        OutputFormat::Text => {
+            for (diagnostic, (file, path)) in diagnostics.iter().zip(&located) {
+                let severity = if diagnostic.severity() == Severity::Error { "error" } else { "warning" };
+                println!("{}: {} at {}: {}", file, severity, path, diagnostic.message());
+            }
+            if diagnostics.is_empty() {
+                println!("{}: no problems found", config_files.join(", "));
+            }
+        }
+        OutputFormat::Json => {
+            let diagnostics: Vec<_> = diagnostics
+                .iter()
+                .zip(&located)
+                .map(|(d, (file, path))| json!({
+                    "severity": if d.severity() == Severity::Error { "error" } else { "warning" },
+                    "file": file,
+                    "path": path,
+                    "message": d.message(),
+                }))
+                .collect();
+            println!("{}", json!(diagnostics));
+        }
+    }
+    let errors = diagnostics.iter().filter(|d| d.severity() == Severity::Error).count();
+    if errors > 0 {
+        return Err(CliError::Config(format!("{} has {} error(s)", config_files.join(", "), errors)));
     }
+    Ok(())
+}
 
-    // Add offset conversions
-    for conv in config.conversions_offset {
-        graph
-            .add_offset_edge(&conv.from, &conv.to, conv.offset)
-            .expect("Error adding offset conversion");
+/// The number format asked for with `--decimals`, `--sig-figs`, `--notation` and `--locale`.
+fn result_format(matches: &ArgMatches) -> Result<ResultFormat, CliError> {
+    let count = |name: &str| -> Result<Option<usize>, CliError> {
+        matches.value_of(name).map(|text| {
+            text.parse()
+                .ok()
+                .filter(|count| *count <= MAX_DECIMAL_PLACES)
+                .ok_or_else(|| CliError::InvalidArgument(format!("Invalid {} '{}', expected at most {}", name, text, MAX_DECIMAL_PLACES)))
+        }).transpose()
+    };
+    let mut number = match matches.value_of("locale") {
+        Some(tag) => NumberFormat::for_locale(tag).ok_or_else(|| CliError::InvalidArgument(format!("Unknown locale '{}'", tag)))?,
+        None => NumberFormat::new(),
+    };
+    number = number.with_notation(match matches.value_of("notation") {
+        Some("scientific") => Notation::Scientific,
+        Some("engineering") => Notation::Engineering,
+        _ => Notation::Plain,
+    });
+    let from_input = matches.value_of("sig-figs") == Some("input");
+    if let Some(places) = count("decimals")? {
+        number = number.with_precision(Precision::Decimals(places));
+    } else if !from_input {
+        if let Some(figures) = count("sig-figs")? {
+            number = number.with_precision(Precision::SignificantFigures(figures));
+        }
     }
+    Ok(ResultFormat { number, from_input })
+}
+
+/// Read one trimmed line from stdin, or `None` at the end of input.
+fn read_input() -> Option<String> {
+    let mut input = String::new();
+    let read = io::stdin()
+        .read_line(&mut input)
+        .expect("Failed to read line");
+    if read == 0 {
+        return None;
+    }
+    Some(input.trim().to_string())
+}
 
+fn print_steps(graph: &ConversionGraph, steps: &[ConversionStep], display: &ResultFormat, figures: usize) {
+    for (index, step) in steps.iter().enumerate() {
+        let hidden = if step.intermediate() { " (intermediate)" } else { "" };
+        println!(
+            "\t{}: {} {} -> {} {}{}  [{}]",
+            index + 1,
+            display.write(graph, step.input(), step.from(), figures),
+            step.from(),
+            display.write(graph, step.output(), step.to(), figures),
+            step.to(),
+            hidden,
+            step.factor()
+        );
+    }
+}
+
+/// The interactive prompt, used when no subcommand is given.
+fn run_repl(graph: &ConversionGraph, display: &ResultFormat) {
     loop {
-        println!("Enter first unit of conversion query or 'exit' to quit:");
-        println!("or type 'list' to list all units");
-        let mut unit1 = String::new();
-        io::stdin()
-            .read_line(&mut unit1)
-            .expect("Failed to read line");
-        let unit1 = unit1.trim();
-
-        if unit1.eq_ignore_ascii_case("exit") {
+        println!("Enter a conversion such as '12.5 km to mi' or '3 ft 4 in -> cm', or 'exit' to quit:");
+        println!("or type 'list [category]' to list units, 'targets <unit> [value]' to list what a unit converts to,");
+        println!("or 'explain <conversion>' to trace a conversion step by step");
+        let Some(input) = read_input() else {
             break;
-        } else if unit1.eq_ignore_ascii_case("list") {
-            println!("Units:");
-            let mut index = 1;
-            for unit in graph.units_formatted(){
-                println!("\t{}: {}", index, unit);
-                index += 1;
+        };
+
+        let (command, rest) = input.split_once(char::is_whitespace).map_or((input.as_str(), ""), |(command, rest)| (command, rest.trim()));
+        if input.eq_ignore_ascii_case("exit") {
+            break;
+        } else if command.eq_ignore_ascii_case("list") {
+            let category = Some(rest).filter(|rest| !rest.is_empty());
+            match unit_groups(graph, category) {
+                Ok(groups) => print_unit_tables(graph, &groups, |_| None),
+                Err(e) => println!("Error: {}", e),
             }
             continue;
-        }
-        if !graph.contains_unit(unit1) {
-            println!("Please enter a valid unit.");
-            continue;
-        }
-        println!("Enter second unit of conversion query:");
-        let mut unit2 = String::new();
-        io::stdin()
-            .read_line(&mut unit2)
-            .expect("Failed to read line");
-        let unit2 = unit2.trim();
-        if unit2.eq_ignore_ascii_case("exit") {
-            break;
-        }
-        if !graph.contains_unit(unit2) {
-            println!("Please enter a valid unit.");
+        } else if command.eq_ignore_ascii_case("targets") {
+            if rest.is_empty() {
+                println!("Error: Expected a unit, such as 'targets km' or 'targets km 12.5'");
+                continue;
+            }
+            let (unit, text) = rest.split_once(char::is_whitespace).map_or((rest, "1"), |(unit, value)| (unit, value.trim()));
+            let Ok(value) = text.parse::<f64>() else {
+                println!("Error: Invalid number '{}'", text);
+                continue;
+            };
+            match graph.convert_to_all(unit, value) {
+                Ok(targets) => print_targets(graph, value, unit, significant_figures(text).unwrap_or(1), &targets, display),
+                Err(e) => println!("Error: {}", graph.with_suggestions(e)),
+            }
             continue;
         }
-        println!("Enter value to convert:");
-        let mut value_str = String::new();
-        io::stdin()
-            .read_line(&mut value_str)
-            .expect("Failed to read line");
-        let value_str = value_str.trim();
-        if value_str.eq_ignore_ascii_case("exit") {
-            break;
-        }
 
-        let value = match value_str.parse::<f64>() {
-            Ok(value) => value,
-            Err(_) => {
-                println!("Please enter a valid number.");
+        let explain = command.eq_ignore_ascii_case("explain");
+        let query_text = if explain { rest } else { input.as_str() };
+        let query: Query = match query_text.parse() {
+            Ok(query) => query,
+            Err(e) => {
+                // point at the offending character
+                println!("\t{}", query_text);
+                println!("\t{}^", " ".repeat(e.position()));
+                println!("Error: {}", e);
                 continue;
             }
         };
 
-        let result = match graph.convert(unit1, unit2, value) {
+        if explain {
+            for term in query.terms() {
+                match graph.explain(term.unit(), query.target(), term.value()) {
+                    Ok(steps) => print_steps(graph, &steps, display, term.significant_figures()),
+                    Err(e) => println!("Cannot explain {} {}: {}", term.value(), term.unit(), graph.with_suggestions(e)),
+                }
+            }
+        }
+
+        let result = match graph.execute(&query) {
             Ok(result) => result,
             Err(e) => {
-                println!("Error: {}", e);
+                println!("Error: {}", graph.with_suggestions(e));
                 continue;
             }
         };
 
-        println!("{} {} = {} {}", value, unit1, result, unit2);
+        let terms: Vec<String> = query.terms().iter().map(|t| format!("{} {}", t.value(), t.unit())).collect();
+        let result = display.write(graph, result, query.target(), query.significant_figures());
+        println!("{} = {} {}", terms.join(" "), result, query.target());
     }
+}
 
+/// The `value` argument (1 if it is optional and not given), and its significant figures
+/// as written.
+fn value_arg(matches: &ArgMatches) -> Result<(f64, usize), CliError> {
+    let text = matches.value_of("value").unwrap_or("1");
+    let value = text
+        .parse()
+        .map_err(|_| CliError::InvalidArgument(format!("Invalid number '{}'", text)))?;
+    Ok((value, significant_figures(text).unwrap_or(1)))
+}
+
+/// The `<value> <from> <to>` arguments shared by `convert` and `explain`, and the
+/// significant figures of the value as written.
+fn conversion_args(matches: &ArgMatches) -> Result<(f64, usize, String, String), CliError> {
+    let (value, figures) = value_arg(matches)?;
+    Ok((value, figures, matches.value_of("from").unwrap().to_string(), matches.value_of("to").unwrap().to_string()))
+}
+
+fn run_convert(graph: &ConversionGraph, matches: &ArgMatches, format: OutputFormat, display: &ResultFormat) -> Result<(), CliError> {
+    let (value, figures, from, to) = conversion_args(matches)?;
+    let result = graph.convert_compound(&from, &to, value)?;
+    let formatted = display.write(graph, result, &to, figures);
+    match format {
+        OutputFormat::Text => println!("{}", formatted),
+        OutputFormat::Json => println!("{}", json!({ "value": value, "from": from, "to": to, "result": result, "formatted": formatted })),
+    }
+    Ok(())
+}
+
+fn run_explain(graph: &ConversionGraph, matches: &ArgMatches, format: OutputFormat, display: &ResultFormat) -> Result<(), CliError> {
+    let (value, figures, from, to) = conversion_args(matches)?;
+    let steps = graph.explain(&from, &to, value)?;
+    let result = steps.last().map_or(value, |step| step.output());
+    match format {
+        OutputFormat::Text => {
+            print_steps(graph, &steps, display, figures);
+            println!("{} {} = {} {}", value, from, display.write(graph, result, &to, figures), to);
+        }
+        OutputFormat::Json => {
+            let steps: Vec<_> = steps
+                .iter()
+                .map(|step| json!({
+                    "from": step.from(),
+                    "to": step.to(),
+                    "scale": step.factor().scale(),
+                    "offset": step.factor().offset(),
+                    "input": step.input(),
+                    "output": step.output(),
+                    "intermediate": step.intermediate(),
+                }))
+                .collect();
+            println!("{}", json!({ "value": value, "from": from, "to": to, "result": result, "steps": steps }));
+        }
+    }
+    Ok(())
+}
+
+fn run_batch(graph: &ConversionGraph, matches: &ArgMatches, format: OutputFormat, display: &ResultFormat) -> Result<(), CliError> {
+    let mut options = BatchOptions::new(matches.value_of("column").unwrap(), matches.value_of("to").unwrap())
+        .with_number_format(display.number.clone());
+    if display.from_input {
+        options = options.with_input_figures();
+    }
+    if let Some(from) = matches.value_of("from") {
+        options = options.with_from_unit(from);
+    }
+    if let Some(unit_column) = matches.value_of("unit-column") {
+        options = options.with_unit_column(unit_column);
+    }
+    if let Some(output_column) = matches.value_of("output-column") {
+        options = options.with_output_column(output_column);
+    }
+    let file = matches.value_of("FILE").filter(|file| *file != "-");
+    if matches.is_present("tsv") || file.is_some_and(|file| file.ends_with(".tsv")) {
+        options = options.with_delimiter(b'\t');
+    }
+
+    let input: Box<dyn Read> = match file {
+        Some(file) => Box::new(
+            fs::File::open(file).map_err(|e| CliError::InvalidArgument(format!("Unable to read {}: {}", file, e)))?,
+        ),
+        None => Box::new(io::stdin()),
+    };
+    let report = graph.convert_table(input, io::stdout(), &options)?;
+
+    match format {
+        OutputFormat::Text => {
+            for error in report.errors() {
+                eprintln!("line {}: {}", error.line(), error.message());
+            }
+        }
+        OutputFormat::Json => {
+            let errors: Vec<_> = report
+                .errors()
+                .iter()
+                .map(|error| json!({ "line": error.line(), "message": error.message() }))
+                .collect();
+            eprintln!("{}", json!({ "rows": report.rows(), "converted": report.converted(), "errors": errors }));
+        }
+    }
+    if report.errors().is_empty() {
+        Ok(())
+    } else {
+        Err(CliError::RowsFailed(report.errors().len(), report.rows()))
+    }
+}
+
+/// The units to list: every visible unit grouped by category, or only the group of `category`.
+fn unit_groups<'a>(graph: &'a ConversionGraph, category: Option<&str>) -> Result<BTreeMap<&'a str, Vec<&'a Unit>>, CliError> {
+    let mut groups = graph.units_by_category();
+    if let Some(category) = category {
+        groups.retain(|name, _| name.eq_ignore_ascii_case(category));
+        if groups.is_empty() {
+            let known = graph.categories().join(", ");
+            return Err(CliError::InvalidArgument(format!("Unknown category '{}', expected one of: {}", category, known)));
+        }
+    }
+    Ok(groups)
+}
+
+/// The names of the units `unit` converts to.
+fn reachable_names<'a>(graph: &'a ConversionGraph, unit: &Unit) -> Vec<&'a str> {
+    graph.reachable_from(unit.name()).unwrap_or_default().into_iter().map(Unit::name).collect()
+}
+
+/// Print the conversion targets of a unit as a table of unit names and values.
+fn print_targets(graph: &ConversionGraph, value: f64, unit: &str, figures: usize, targets: &[(&Unit, f64)], display: &ResultFormat) {
+    if targets.is_empty() {
+        println!("{} cannot be converted to any other unit", unit);
+        return;
+    }
+    println!("{} {} is", value, unit);
+    let width = targets.iter().map(|(target, _)| target.name().chars().count()).max().unwrap_or(0);
+    for (target, result) in targets {
+        println!("    {:<width$}  {}", target.name(), display.write(graph, *result, target.name(), figures));
+    }
+}
+
+/// Print every group as a table of the units with their aliases, the units they convert
+/// to and, if `source` gives one, the config file they come from.
+fn print_unit_tables(graph: &ConversionGraph, groups: &BTreeMap<&str, Vec<&Unit>>, source: impl Fn(&str) -> Option<String>) {
+    for (index, (category, units)) in groups.iter().enumerate() {
+        let rows: Vec<[String; 4]> = units
+            .iter()
+            .map(|unit| [
+                unit.name().to_string(),
+                unit.aliases().iter().filter(|alias| *alias != unit.name()).cloned().collect::<Vec<_>>().join(", "),
+                reachable_names(graph, unit).join(", "),
+                source(unit.name()).unwrap_or_default(),
+            ])
+            .collect();
+        let sourced = rows.iter().any(|row| !row[3].is_empty());
+        let header = ["unit", "aliases", "converts to", if sourced { "source" } else { "" }].map(String::from);
+        let width = |column: usize| rows.iter().chain([&header]).map(|row| row[column].chars().count()).max().unwrap_or(0);
+        let (name_width, alias_width, target_width) = (width(0), width(1), width(2));
+
+        if index > 0 {
+            println!();
+        }
+        println!("{} ({})", category, units.len());
+        for row in [&header].into_iter().chain(&rows) {
+            let line = format!("    {:<name_width$}  {:<alias_width$}  {:<target_width$}  {}", row[0], row[1], row[2], row[3]);
+            println!("{}", line.trim_end());
+        }
+    }
+}
+
+fn run_targets(graph: &ConversionGraph, matches: &ArgMatches, format: OutputFormat, display: &ResultFormat) -> Result<(), CliError> {
+    let unit = matches.value_of("unit").unwrap();
+    let (value, figures) = value_arg(matches)?;
+    let targets = graph.convert_to_all(unit, value)?;
+    match format {
+        OutputFormat::Text => print_targets(graph, value, unit, figures, &targets, display),
+        OutputFormat::Json => {
+            let targets: Vec<_> = targets
+                .iter()
+                .map(|(target, result)| json!({
+                    "unit": target.name(),
+                    "result": result,
+                    "formatted": display.write(graph, *result, target.name(), figures),
+                }))
+                .collect();
+            println!("{}", json!({ "value": value, "from": unit, "targets": targets }));
+        }
+    }
+    Ok(())
+}
+
+fn run_table(graph: &ConversionGraph, matches: &ArgMatches, format: OutputFormat, display: &ResultFormat) -> Result<(), CliError> {
+    let (value, figures) = value_arg(matches)?;
+    let from = matches.value_of("from").unwrap();
+    let mut results = graph.convert_to_all(from, value)?;
+    results.sort_by(|(_, a), (_, b)| a.abs().total_cmp(&b.abs()));
+    let rows: Vec<(String, &Unit, f64)> = results
+        .into_iter()
+        .map(|(unit, result)| (display.write(graph, result, unit.name(), figures), unit, result))
+        .collect();
+    match format {
+        OutputFormat::Text => {
+            let width = rows.iter().map(|(formatted, _, _)| formatted.chars().count()).max().unwrap_or(0);
+            for (formatted, unit, _) in &rows {
+                println!("{:>width$}  {}", formatted, unit.name());
+            }
+        }
+        OutputFormat::Json => {
+            let rows: Vec<_> = rows
+                .iter()
+                .map(|(formatted, unit, result)| json!({ "unit": unit.name(), "result": result, "formatted": formatted }))
+                .collect();
+            println!("{}", json!({ "value": value, "from": from, "results": rows }));
+        }
+    }
+    Ok(())
+}
+
+fn run_list(graph: &ConversionGraph, catalog: &CatalogLoader, category: Option<&str>, sources: bool, format: OutputFormat) -> Result<(), CliError> {
+    let groups = unit_groups(graph, category)?;
+    let source = |name: &str| catalog.source_of(name).map(|file| file.display().to_string());
+    match format {
+        OutputFormat::Text => print_unit_tables(graph, &groups, |name| source(name).filter(|_| sources)),
+        OutputFormat::Json => {
+            let units: Vec<_> = groups
+                .values()
+                .flatten()
+                .map(|unit| json!({
+                    "name": unit.name(),
+                    "aliases": unit.aliases(),
+                    "category": unit.category(),
+                    "dimension": unit.dimension().map(|d| d.to_string()),
+                    "converts_to": reachable_names(graph, unit),
+                    "source": source(unit.name()),
+                }))
+                .collect();
+            println!("{}", json!(units));
+        }
+    }
+    Ok(())
+}
+
+/// Run the subcommand given on the command line, or the interactive prompt without one.
+fn run_command(graph: &ConversionGraph, catalog: &CatalogLoader, matches: &ArgMatches, format: OutputFormat) -> Result<(), CliError> {
+    let display = result_format(matches)?;
+    match matches.subcommand() {
+        ("convert", Some(sub)) => run_convert(graph, sub, format, &display),
+        ("explain", Some(sub)) => run_explain(graph, sub, format, &display),
+        ("batch", Some(sub)) => run_batch(graph, sub, format, &display),
+        ("targets", Some(sub)) => run_targets(graph, sub, format, &display),
+        ("table", Some(sub)) => run_table(graph, sub, format, &display),
+        ("list", Some(sub)) => run_list(graph, catalog, sub.value_of("category"), sub.is_present("sources"), format),
+        _ => {
+            run_repl(graph, &display);
+            Ok(())
+        }
+    }
+}
+
+fn main() {
+    let conversion = |name: &'static str, about: &'static str| {
+        SubCommand::with_name(name)
+            .about(about)
+            .setting(AppSettings::AllowNegativeNumbers)
+            .arg(Arg::with_name("value").help("The value to convert").required(true).index(1))
+            .arg(Arg::with_name("from").help("The unit to convert from").required(true).index(2))
+            .arg(Arg::with_name("to").help("The unit to convert to").required(true).index(3))
+    };
+    let matches = App::new("Conversion Wizard")
+        .version("1.0")
+        .about("Converts between units of measurement")
+        .arg(
+            Arg::with_name("config")
+                .short("c")
+                .long("config")
+                .value_name("FILE")
+                .help("Sets a custom config file; repeat to layer several files, later ones on top")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("std")
+                .long("std")
+                .help("Loads the built-in standard unit catalog, beneath any config files")
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("exact")
+                .long("exact")
+                .help("Composes conversion factors as exact fractions, rounding only the result")
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("ignore-case")
+                .long("ignore-case")
+                .short("i")
+                .help("Matches units regardless of case when the spelling is not ambiguous, e.g. KM or celsius")
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("config-format")
+                .long("config-format")
+                .value_name("FORMAT")
+                .help("Format of the config files [default: from their extension, else json]")
+                .possible_values(&["json", "toml", "yaml"])
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("format")
+                .long("format")
+                .value_name("FORMAT")
+                .help("Output format of subcommands")
+                .possible_values(&["text", "json"])
+                .default_value("text")
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("duplicate-edges")
+                .long("duplicate-edges")
+                .value_name("POLICY")
+                .help("What a repeated conversion between two units does")
+                .possible_values(&["error", "warn", "replace", "merge"])
+                .default_value("warn")
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("decimals")
+                .long("decimals")
+                .value_name("N")
+                .help("Writes results with N digits after the decimal point")
+                .conflicts_with("sig-figs")
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("sig-figs")
+                .long("sig-figs")
+                .value_name("N")
+                .help("Writes results with N significant figures, or as many as the input has with 'input'")
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("notation")
+                .long("notation")
+                .value_name("NOTATION")
+                .help("How the magnitude of results is written")
+                .possible_values(&["plain", "scientific", "engineering"])
+                .default_value("plain")
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("locale")
+                .long("locale")
+                .value_name("TAG")
+                .help("Writes results with the separators of a locale, e.g. en-US or de-DE")
+                .global(true),
+        )
+        .subcommand(conversion("convert", "Converts a value and prints the result"))
+        .subcommand(conversion("explain", "Prints every step of a conversion"))
+        .subcommand(
+            SubCommand::with_name("list")
+                .about("Lists all units grouped by category, with the units each converts to")
+                .arg(Arg::with_name("category").help("Only lists the units of this category").index(1))
+                .arg(Arg::with_name("sources").long("sources").help("Shows the config file each unit comes from")),
+        )
+        .subcommand(
+            SubCommand::with_name("targets")
+                .about("Lists every unit a unit converts to, with a value converted to each")
+                .setting(AppSettings::AllowNegativeNumbers)
+                .arg(Arg::with_name("unit").help("The unit to convert from").required(true).index(1))
+                .arg(Arg::with_name("value").help("The value to convert [default: 1]").index(2)),
+        )
+        .subcommand(
+            SubCommand::with_name("table")
+                .about("Converts a value to every unit it converts to, smallest result first")
+                .setting(AppSettings::AllowNegativeNumbers)
+                .arg(Arg::with_name("value").help("The value to convert").required(true).index(1))
+                .arg(Arg::with_name("from").help("The unit to convert from").required(true).index(2)),
+        )
+        .subcommand(SubCommand::with_name("validate").about("Checks the config file and reports every problem in it"))
+        .subcommand(
+            SubCommand::with_name("batch")
+                .about("Converts a column of a CSV or TSV table and prints the table with the results added")
+                .arg(Arg::with_name("FILE").help("The table to read, or - for stdin (the default)").index(1))
+                .arg(Arg::with_name("column").long("column").value_name("COLUMN").help("The column to convert").required(true))
+                .arg(Arg::with_name("to").long("to").value_name("UNIT").help("The unit to convert to").required(true))
+                .arg(
+                    Arg::with_name("from")
+                        .long("from")
+                        .value_name("UNIT")
+                        .help("The unit of the values, for rows without a unit column value")
+                        .required_unless("unit-column"),
+                )
+                .arg(Arg::with_name("unit-column").long("unit-column").value_name("COLUMN").help("A column holding the unit of each row"))
+                .arg(Arg::with_name("output-column").long("output-column").value_name("COLUMN").help("Name of the added column"))
+                .arg(Arg::with_name("tsv").long("tsv").help("Read and write tab separated values (implied by a .tsv file)")),
+        )
+        .get_matches();
+
+    let standard = matches.is_present("std");
+    let config_files: Vec<&str> = match matches.values_of("config") {
+        Some(files) => files.collect(),
+        None if standard => Vec::new(),
+        None => vec!["data.json"],
+    };
+    let format = match matches.value_of("format") {
+        Some("json") => OutputFormat::Json,
+        _ => OutputFormat::Text,
+    };
+
+    let config_format = matches.value_of("config-format").and_then(ConfigFormat::from_extension);
+    let policy = match matches.value_of("duplicate-edges") {
+        Some("error") => DuplicateEdgePolicy::Error,
+        Some("replace") => DuplicateEdgePolicy::Replace,
+        Some("merge") => DuplicateEdgePolicy::Merge,
+        _ => DuplicateEdgePolicy::Warn,
+    };
+
+    // every layer, for messages
+    let layers: Vec<&str> = standard.then_some(STANDARD_SOURCE).into_iter().chain(config_files.iter().copied()).collect();
+    let result = load_catalog(standard, &config_files, config_format).and_then(|catalog| {
+        if matches.subcommand_name() == Some("validate") {
+            return run_validate(&catalog, &layers, policy, format);
+        }
+        let lookup = UnitLookup::new().with_case_folding(matches.is_present("ignore-case"));
+        let mut graph = load_graph(&catalog, &layers, policy, matches.is_present("exact"), lookup)?;
+        print_warnings(&mut graph, format);
+        run_command(&graph, &catalog, &matches, format).map_err(|e| match e {
+            CliError::Conversion(e) => CliError::Conversion(graph.with_suggestions(e)),
+            e => e,
+        })
+    });
+
+    if let Err(e) = result {
+        match format {
+            OutputFormat::Text => eprintln!("Error: {}", e),
+            OutputFormat::Json => {
+                let mut error = json!({ "kind": e.kind(), "message": e.to_string() });
+                if !e.suggestions().is_empty() {
+                    error["suggestions"] = json!(e.suggestions());
+                }
+                eprintln!("{}", json!({ "error": error }));
+            }
+        }
+        process::exit(e.exit_code());
+    }
 }
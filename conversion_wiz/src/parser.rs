@@ -0,0 +1,395 @@
+//! Parser for one-line conversion queries such as `12.5 km to mi`.
+
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::{significant_figures, ConversionError, ConversionGraph};
+
+/// Words and symbols separating the quantity from the target unit.
+const KEYWORDS: [&str; 3] = ["to", "in", "as"];
+const ARROWS: [&str; 3] = ["->", "=>", "→"];
+
+/// `ParseErrorKind` enum to represent what went wrong while parsing a `Query`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseErrorKind {
+    EmptyQuery,
+    InvalidNumber,
+    ExpectedNumber,
+    ExpectedUnit,
+    MissingSeparator,
+    MissingTarget,
+}
+
+/// `ParseError` struct to represent a malformed query and where in it the problem is.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    kind: ParseErrorKind,
+    /// character offset into the query, starting at 0
+    position: usize,
+}
+
+impl ParseError {
+    pub fn kind(&self) -> ParseErrorKind {
+        self.kind
+    }
+
+    /// Character offset into the query where the problem is, starting at 0.
+    pub fn position(&self) -> usize {
+        self.position
+    }
+}
+
+impl Error for ParseError {}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let message = match self.kind {
+            ParseErrorKind::EmptyQuery => "Query is empty",
+            ParseErrorKind::InvalidNumber => "Invalid number",
+            ParseErrorKind::ExpectedNumber => "Expected a number",
+            ParseErrorKind::ExpectedUnit => "Expected a unit",
+            ParseErrorKind::MissingSeparator => "Expected 'to', 'in', 'as' or '->' followed by a unit",
+            ParseErrorKind::MissingTarget => "Expected a target unit",
+        };
+        write!(f, "{} at column {}", message, self.position + 1)
+    }
+}
+
+/// `Quantity` struct to represent a value with its unit, e.g. `12.5 km`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Quantity {
+    value: f64,
+    unit: String,
+    /// significant figures of the value as written (see `significant_figures`)
+    significant_figures: usize,
+}
+
+impl Quantity {
+    pub fn value(&self) -> f64 {
+        self.value
+    }
+
+    /// The significant figures of the value as written, so 4 for `12.50 m`.
+    pub fn significant_figures(&self) -> usize {
+        self.significant_figures
+    }
+
+    /// The unit as written, which may be a compound unit expression such as `km/h`.
+    pub fn unit(&self) -> &str {
+        &self.unit
+    }
+}
+
+/// `Query` struct to represent a parsed conversion query: one or more quantities that are
+/// added together (`3 ft 4 in`) and the unit to express the sum in.
+///
+/// Parsed from text such as `12.5 km to mi`, `98.6°F in C`, `3 ft 4 in -> cm` or `1e3 m as km`.
+/// The separator is the last `to`, `in`, `as`, `->`, `=>` or `→` that is followed by a unit,
+/// so `in` still works as a unit (inch) before it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Query {
+    terms: Vec<Quantity>,
+    target: String,
+}
+
+impl Query {
+    /// The quantities to convert, in the order they were written.
+    pub fn terms(&self) -> &[Quantity] {
+        &self.terms
+    }
+
+    /// The unit to convert to.
+    pub fn target(&self) -> &str {
+        &self.target
+    }
+
+    /// The significant figures a result of the query can claim: the fewest of any term.
+    pub fn significant_figures(&self) -> usize {
+        self.terms.iter().map(Quantity::significant_figures).min().unwrap_or(1)
+    }
+}
+
+impl fmt::Display for Query {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let terms: Vec<String> = self.terms.iter().map(|t| format!("{} {}", t.value, t.unit)).collect();
+        write!(f, "{} to {}", terms.join(" "), self.target)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum TokenKind {
+    Number(f64),
+    Word,
+    Arrow,
+}
+
+#[derive(Debug)]
+struct Token {
+    kind: TokenKind,
+    start: usize,
+    end: usize,
+}
+
+impl Token {
+    fn is_separator(&self, chars: &[char]) -> bool {
+        match self.kind {
+            TokenKind::Arrow => true,
+            TokenKind::Word => {
+                let word: String = chars[self.start..self.end].iter().collect();
+                KEYWORDS.iter().any(|k| k.eq_ignore_ascii_case(&word))
+            }
+            TokenKind::Number(_) => false,
+        }
+    }
+}
+
+fn arrow_at(chars: &[char], pos: usize) -> Option<usize> {
+    ARROWS.iter()
+        .map(|arrow| arrow.chars().collect::<Vec<_>>())
+        .find(|arrow| chars[pos..].starts_with(arrow))
+        .map(|arrow| arrow.len())
+}
+
+/// Length of the number starting at `pos`, if one starts there:
+/// sign? digits ('.' digits)? (('e' | 'E') sign? digits)?
+fn number_at(chars: &[char], pos: usize) -> Option<usize> {
+    let digits_from = |mut i: usize| {
+        while chars.get(i).is_some_and(char::is_ascii_digit) {
+            i += 1;
+        }
+        i
+    };
+    let mut i = pos;
+    if matches!(chars.get(i), Some('+' | '-')) {
+        i += 1;
+    }
+    let int_end = digits_from(i);
+    let mut end = int_end;
+    if chars.get(end) == Some(&'.') {
+        end = digits_from(end + 1);
+    }
+    // need at least one digit in the mantissa
+    if end - i == 0 || (int_end == i && end == int_end + 1) {
+        return None;
+    }
+    if matches!(chars.get(end), Some('e' | 'E')) {
+        let mut j = end + 1;
+        if matches!(chars.get(j), Some('+' | '-')) {
+            j += 1;
+        }
+        let exp_end = digits_from(j);
+        if exp_end > j {
+            end = exp_end;
+        }
+    }
+    Some(end - pos)
+}
+
+fn tokenize(chars: &[char]) -> Result<Vec<Token>, ParseError> {
+    let mut tokens = Vec::new();
+    let mut pos = 0;
+    while pos < chars.len() {
+        if chars[pos].is_whitespace() {
+            pos += 1;
+        } else if let Some(len) = arrow_at(chars, pos) {
+            tokens.push(Token { kind: TokenKind::Arrow, start: pos, end: pos + len });
+            pos += len;
+        } else if let Some(len) = number_at(chars, pos) {
+            let text: String = chars[pos..pos + len].iter().collect();
+            let value = text.parse::<f64>()
+                .map_err(|_| ParseError { kind: ParseErrorKind::InvalidNumber, position: pos })?;
+            tokens.push(Token { kind: TokenKind::Number(value), start: pos, end: pos + len });
+            pos += len;
+        } else {
+            let start = pos;
+            while pos < chars.len() && !chars[pos].is_whitespace() && arrow_at(chars, pos).is_none() {
+                pos += 1;
+            }
+            tokens.push(Token { kind: TokenKind::Word, start, end: pos });
+        }
+    }
+    Ok(tokens)
+}
+
+impl FromStr for Query {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let chars: Vec<char> = s.chars().collect();
+        let tokens = tokenize(&chars)?;
+        if tokens.is_empty() {
+            return Err(ParseError { kind: ParseErrorKind::EmptyQuery, position: 0 });
+        }
+        let text = |start: usize, end: usize| chars[start..end].iter().collect::<String>();
+
+        // the separator is the last one followed only by unit words
+        let separator = (1..tokens.len()).rev().find(|&i| {
+            tokens[i].is_separator(&chars)
+                && i + 1 < tokens.len()
+                && tokens[i + 1..].iter().all(|t| t.kind == TokenKind::Word)
+        });
+        let Some(separator) = separator else {
+            let kind = if tokens.last().is_some_and(|t| t.is_separator(&chars)) {
+                ParseErrorKind::MissingTarget
+            } else {
+                ParseErrorKind::MissingSeparator
+            };
+            return Err(ParseError { kind, position: chars.len() });
+        };
+        let target = text(tokens[separator + 1].start, tokens[tokens.len() - 1].end);
+
+        let mut terms = Vec::new();
+        let mut i = 0;
+        while i < separator {
+            let TokenKind::Number(value) = tokens[i].kind else {
+                return Err(ParseError { kind: ParseErrorKind::ExpectedNumber, position: tokens[i].start });
+            };
+            let unit_start = i + 1;
+            let mut unit_end = unit_start;
+            while unit_end < separator && tokens[unit_end].kind == TokenKind::Word {
+                unit_end += 1;
+            }
+            if unit_end == unit_start {
+                let position = tokens.get(unit_start).map_or(tokens[i].end, |t| t.start);
+                return Err(ParseError { kind: ParseErrorKind::ExpectedUnit, position });
+            }
+            terms.push(Quantity {
+                value,
+                unit: text(tokens[unit_start].start, tokens[unit_end - 1].end),
+                significant_figures: significant_figures(&text(tokens[i].start, tokens[i].end)).unwrap_or(1),
+            });
+            i = unit_end;
+        }
+
+        Ok(Query { terms, target })
+    }
+}
+
+/// Query execution.
+impl ConversionGraph {
+    /// Convert every quantity of the query to the target unit and add them up.
+    /// Units may be compound expressions (see `convert_compound`), and a leading degree
+    /// sign is ignored if the unit is not found with it (`°F` is looked up as `F`).
+    ///
+    /// # Error
+    ///
+    /// Error if a unit does not exist or cannot be converted to the target. Quantities that
+    /// need an offset conversion (such as temperatures) cannot be added together.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use conversion_wiz::{ConversionGraph, Query};
+    ///
+    /// let mut graph = ConversionGraph::new();
+    /// graph.add_unit("foot", vec!["ft"], false).unwrap();
+    /// graph.add_unit("inch", vec!["in"], false).unwrap();
+    /// graph.add_edge("ft", "in", 12.0, 0.0).unwrap();
+    /// let query: Query = "3 ft 4 in to in".parse().unwrap();
+    /// assert_eq!(graph.execute(&query).unwrap(), 40.0);
+    /// ```
+    pub fn execute(&self, query: &Query) -> Result<f64, ConversionError> {
+        let target = self.query_unit(query.target())?;
+        let mut total = 0.0;
+        for term in query.terms() {
+            let unit = self.query_unit(term.unit())?;
+            let factor = self.factor_between_exprs(&unit, &target)?;
+            if query.terms().len() > 1 && factor.offset() != 0.0 {
+                return Err(ConversionError::OffsetUnitInCompound(term.unit().to_string()));
+            }
+            total += factor.apply(term.value());
+        }
+        Ok(total)
+    }
+
+    /// Parse a unit written in a query, dropping a leading degree sign if needed.
+    fn query_unit(&self, unit: &str) -> Result<crate::UnitExpr, ConversionError> {
+        match unit.strip_prefix('°') {
+            Some(stripped) if !self.contains_unit(unit) && self.contains_unit(stripped) => self.unit_expr(stripped),
+            _ => self.unit_expr(unit),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+
+    const REL_TOL: f64 = 1e-9;
+
+    fn parse(s: &str) -> Query {
+        s.parse().expect("Query should parse")
+    }
+
+    fn error(s: &str) -> ParseError {
+        s.parse::<Query>().expect_err("Query should not parse")
+    }
+
+    #[test]
+    fn test_query_parse() {
+        let query = parse("12.5 km to mi");
+        assert_eq!(query.terms().len(), 1);
+        assert_eq!((query.terms()[0].value(), query.terms()[0].unit()), (12.5, "km"));
+        assert_eq!(query.target(), "mi");
+
+        let query = parse("98.6°F in C");
+        assert_eq!((query.terms()[0].value(), query.terms()[0].unit()), (98.6, "°F"));
+        assert_eq!(query.target(), "C");
+
+        let query = parse("1e3 m as km");
+        assert_eq!(query.terms()[0].value(), 1000.0);
+
+        let query = parse("-40 Vietnamese Meter => km/h");
+        assert_eq!((query.terms()[0].value(), query.terms()[0].unit()), (-40.0, "Vietnamese Meter"));
+        assert_eq!(query.target(), "km/h");
+    }
+
+    #[test]
+    fn test_query_parse_multiple_terms() {
+        let query = parse("3 ft 4 in -> cm");
+        let terms: Vec<(f64, &str)> = query.terms().iter().map(|t| (t.value(), t.unit())).collect();
+        assert_eq!(terms, vec![(3.0, "ft"), (4.0, "in")]);
+        assert_eq!(query.target(), "cm");
+
+        let query = parse("12.50 m 0.3 cm to in");
+        assert_eq!(query.terms()[0].significant_figures(), 4);
+        assert_eq!(query.significant_figures(), 1);
+
+        // "in" is a unit on either side of the separator
+        let query = parse("5 cm in in");
+        assert_eq!((query.terms()[0].unit(), query.target()), ("cm", "in"));
+        let query = parse("5 in to cm");
+        assert_eq!((query.terms()[0].unit(), query.target()), ("in", "cm"));
+    }
+
+    #[test]
+    fn test_query_parse_errors() {
+        assert_eq!(error("   "), ParseError { kind: ParseErrorKind::EmptyQuery, position: 0 });
+        assert_eq!(error("12 km"), ParseError { kind: ParseErrorKind::MissingSeparator, position: 5 });
+        assert_eq!(error("12 km to"), ParseError { kind: ParseErrorKind::MissingTarget, position: 8 });
+        assert_eq!(error("km to m"), ParseError { kind: ParseErrorKind::ExpectedNumber, position: 0 });
+        assert_eq!(error("3 ft 4 -> cm"), ParseError { kind: ParseErrorKind::ExpectedUnit, position: 7 });
+        assert_eq!(error("12 km to 3 m").kind(), ParseErrorKind::MissingSeparator);
+        assert_eq!(error("12 km to").to_string(), "Expected a target unit at column 9");
+    }
+
+    #[test]
+    fn test_execute_query() {
+        let mut graph = ConversionGraph::new();
+        let _ = graph.add_unit("foot", vec!["ft"], false);
+        let _ = graph.add_unit("inch", vec!["in"], false);
+        let _ = graph.add_unit("centimeter", vec!["cm"], false);
+        let _ = graph.add_unit("Celsius", vec!["C"], false);
+        let _ = graph.add_unit("Fahrenheit", vec!["F"], false);
+        let _ = graph.add_edge("ft", "in", 12.0, 0.0);
+        let _ = graph.add_edge("in", "cm", 2.54, 0.0);
+        let _ = graph.add_edge("C", "F", 1.8, 32.0);
+
+        assert_relative_eq!(graph.execute(&parse("3 ft 4 in -> cm")).unwrap(), 101.6, max_relative = REL_TOL);
+        assert_relative_eq!(graph.execute(&parse("98.6°F in C")).unwrap(), 37.0, max_relative = REL_TOL);
+        assert!(matches!(graph.execute(&parse("1 C 2 C to F")), Err(ConversionError::OffsetUnitInCompound(_))));
+        assert!(matches!(graph.execute(&parse("1 yd to ft")), Err(ConversionError::UnitNotFound { .. })));
+    }
+}
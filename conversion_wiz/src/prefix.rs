@@ -0,0 +1,170 @@
+//! SI and binary unit prefixes.
+
+/// `PrefixKind` enum to distinguish decimal SI prefixes (kilo = 1000) from
+/// binary prefixes (kibi = 1024) used for information units.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PrefixKind {
+    Si,
+    Binary,
+}
+
+/// `Prefix` struct to represent a unit prefix such as kilo (k, 10^3) or kibi (Ki, 2^10).
+/// Prefixes are never stored as units or edges; a prefixed alias such as `km` is
+/// resolved on lookup to its prefixable base unit and the prefix factor.
+#[derive(Debug, PartialEq)]
+pub struct Prefix {
+    name: &'static str,
+    symbols: &'static [&'static str],
+    factor: f64,
+    kind: PrefixKind,
+}
+
+impl Prefix {
+    const fn new(name: &'static str, symbols: &'static [&'static str], factor: f64, kind: PrefixKind) -> Self {
+        Self { name, symbols, factor, kind }
+    }
+
+    /// The full name of the prefix, e.g. "kilo".
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// The main symbol of the prefix, e.g. "k".
+    pub fn symbol(&self) -> &'static str {
+        self.symbols[0]
+    }
+
+    /// How many base units one prefixed unit is, e.g. 1000 for kilo.
+    pub fn factor(&self) -> f64 {
+        self.factor
+    }
+
+    pub fn kind(&self) -> PrefixKind {
+        self.kind
+    }
+
+    /// The full name followed by every symbol.
+    pub(crate) fn spellings(&self) -> impl Iterator<Item = &'static str> {
+        std::iter::once(self.name).chain(self.symbols.iter().copied())
+    }
+
+    /// Whether `spelling` of the prefix can go in front of the unit spelling `unit`: the
+    /// full name only in front of a full unit name (`kilometer`), and a symbol only in
+    /// front of a unit symbol (`km`), so `kilom` and `kmeter` are not units.
+    pub(crate) fn combines(&self, spelling: &str, unit: &str) -> bool {
+        match spelling == self.name {
+            true => is_unit_name(unit),
+            false => is_unit_symbol(unit),
+        }
+    }
+}
+
+/// A unit spelling is a full name if it is a word of at least three letters (`meter`,
+/// `watt hour`), and a symbol if it has at most three characters or is not a plain word
+/// (`m`, `Hz`, `m3`). Three-letter words such as `bit` and `bar` are both, as they are
+/// written the same either way.
+fn is_unit_name(unit: &str) -> bool {
+    unit.chars().count() >= 3 && unit.chars().all(|c| c.is_alphabetic() || c == ' ')
+}
+
+fn is_unit_symbol(unit: &str) -> bool {
+    unit.chars().count() <= 3 || !is_unit_name(unit)
+}
+
+/// All SI prefixes (2022 revision) followed by the IEC binary prefixes.
+pub const PREFIXES: [Prefix; 32] = [
+    Prefix::new("quetta", &["Q"], 1e30, PrefixKind::Si),
+    Prefix::new("ronna", &["R"], 1e27, PrefixKind::Si),
+    Prefix::new("yotta", &["Y"], 1e24, PrefixKind::Si),
+    Prefix::new("zetta", &["Z"], 1e21, PrefixKind::Si),
+    Prefix::new("exa", &["E"], 1e18, PrefixKind::Si),
+    Prefix::new("peta", &["P"], 1e15, PrefixKind::Si),
+    Prefix::new("tera", &["T"], 1e12, PrefixKind::Si),
+    Prefix::new("giga", &["G"], 1e9, PrefixKind::Si),
+    Prefix::new("mega", &["M"], 1e6, PrefixKind::Si),
+    Prefix::new("kilo", &["k"], 1e3, PrefixKind::Si),
+    Prefix::new("hecto", &["h"], 1e2, PrefixKind::Si),
+    Prefix::new("deca", &["da"], 1e1, PrefixKind::Si),
+    Prefix::new("deci", &["d"], 1e-1, PrefixKind::Si),
+    Prefix::new("centi", &["c"], 1e-2, PrefixKind::Si),
+    Prefix::new("milli", &["m"], 1e-3, PrefixKind::Si),
+    Prefix::new("micro", &["µ", "μ", "u"], 1e-6, PrefixKind::Si),
+    Prefix::new("nano", &["n"], 1e-9, PrefixKind::Si),
+    Prefix::new("pico", &["p"], 1e-12, PrefixKind::Si),
+    Prefix::new("femto", &["f"], 1e-15, PrefixKind::Si),
+    Prefix::new("atto", &["a"], 1e-18, PrefixKind::Si),
+    Prefix::new("zepto", &["z"], 1e-21, PrefixKind::Si),
+    Prefix::new("yocto", &["y"], 1e-24, PrefixKind::Si),
+    Prefix::new("ronto", &["r"], 1e-27, PrefixKind::Si),
+    Prefix::new("quecto", &["q"], 1e-30, PrefixKind::Si),
+    Prefix::new("kibi", &["Ki"], 1024.0, PrefixKind::Binary),
+    Prefix::new("mebi", &["Mi"], 1048576.0, PrefixKind::Binary),
+    Prefix::new("gibi", &["Gi"], 1073741824.0, PrefixKind::Binary),
+    Prefix::new("tebi", &["Ti"], 1099511627776.0, PrefixKind::Binary),
+    Prefix::new("pebi", &["Pi"], 1125899906842624.0, PrefixKind::Binary),
+    Prefix::new("exbi", &["Ei"], 1152921504606846976.0, PrefixKind::Binary),
+    Prefix::new("zebi", &["Zi"], 1180591620717411303424.0, PrefixKind::Binary),
+    Prefix::new("yobi", &["Yi"], 1208925819614629174706176.0, PrefixKind::Binary),
+];
+
+/// Split `alias` into a prefix and the rest, trying longer spellings first
+/// (so "dam" is deca-meter, not deci-"am"). `accepts` decides whether the rest
+/// names a unit that takes prefixes of the given kind; the rest must also be the same
+/// form as the prefix (see `Prefix::combines`).
+pub(crate) fn split_prefix(alias: &str, mut accepts: impl FnMut(&str, PrefixKind) -> bool) -> Option<(&'static Prefix, &str)> {
+    let mut candidates: Vec<(&'static Prefix, &'static str)> = PREFIXES.iter()
+        .flat_map(|prefix| prefix.spellings().map(move |spelling| (prefix, spelling)))
+        .filter(|(_, spelling)| alias.len() > spelling.len() && alias.starts_with(spelling))
+        .collect();
+    candidates.sort_by_key(|(_, spelling)| std::cmp::Reverse(spelling.len()));
+    candidates.into_iter()
+        .map(|(prefix, spelling)| (prefix, spelling, &alias[spelling.len()..]))
+        .find(|(prefix, spelling, rest)| prefix.combines(spelling, rest) && accepts(rest, prefix.kind))
+        .map(|(prefix, _, rest)| (prefix, rest))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_prefix() {
+        let meter = |rest: &str, kind: PrefixKind| (rest == "m" || rest == "meter") && kind == PrefixKind::Si;
+        let (prefix, rest) = split_prefix("km", meter).unwrap();
+        assert_eq!((prefix.name(), rest), ("kilo", "m"));
+        let (prefix, rest) = split_prefix("kilometer", meter).unwrap();
+        assert_eq!((prefix.symbol(), rest), ("k", "meter"));
+        assert_eq!(split_prefix("dam", meter).unwrap().0.name(), "deca");
+        assert_eq!(split_prefix("µm", meter).unwrap().0.name(), "micro");
+        assert_eq!(split_prefix("um", meter).unwrap().0.name(), "micro");
+        assert!(split_prefix("m", meter).is_none());
+        assert!(split_prefix("Kim", meter).is_none());
+    }
+
+    #[test]
+    fn test_split_prefix_mixed_forms() {
+        let meter = |rest: &str, _: PrefixKind| rest == "m" || rest == "meter" || rest == "watt hour";
+        assert!(split_prefix("kilom", meter).is_none());
+        assert!(split_prefix("kmeter", meter).is_none());
+        assert!(split_prefix("microm", meter).is_none());
+        assert!(split_prefix("µmeter", meter).is_none());
+        assert!(split_prefix("kwatt hour", meter).is_none());
+        assert_eq!(split_prefix("kilowatt hour", meter).unwrap().1, "watt hour");
+        // three-letter names are also their symbols
+        let bit = |rest: &str, _: PrefixKind| rest == "bit" || rest == "b";
+        assert_eq!(split_prefix("kbit", bit).unwrap().1, "bit");
+        assert_eq!(split_prefix("kilobit", bit).unwrap().1, "bit");
+        assert_eq!(split_prefix("Mibit", bit).unwrap().0.name(), "mebi");
+        assert!(split_prefix("kilob", bit).is_none());
+    }
+
+    #[test]
+    fn test_split_binary_prefix() {
+        let byte = |rest: &str, _: PrefixKind| rest == "B";
+        assert_eq!(split_prefix("KiB", byte).unwrap().0.factor(), 1024.0);
+        assert_eq!(split_prefix("MiB", byte).unwrap().0.factor(), 1048576.0);
+        assert_eq!(split_prefix("MB", byte).unwrap().0.factor(), 1e6);
+        let si_only = |rest: &str, kind: PrefixKind| rest == "B" && kind == PrefixKind::Si;
+        assert!(split_prefix("KiB", si_only).is_none());
+    }
+}
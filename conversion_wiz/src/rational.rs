@@ -0,0 +1,319 @@
+//! Exact conversion factors, with big integer numerators and denominators.
+
+use std::fmt;
+
+use num_bigint::BigInt;
+use num_rational::BigRational;
+use num_traits::{One, Signed, ToPrimitive, Zero};
+
+use crate::{ConversionError, ConversionFactor, ConversionGraph};
+
+/// Largest power of ten, up or down, `parse_rational` accepts in an exponent, so a
+/// number such as `1e999999999` cannot ask for a huge integer.
+const MAX_EXPONENT: i32 = 4096;
+
+/// Most decimal places `format_decimal` writes; more are clamped to this.
+pub const MAX_DECIMAL_PLACES: usize = 4096;
+
+/// `RationalFactor` struct to represent the affine map `to = scale * from + offset` with
+/// exact rational numbers, the counterpart of `ConversionFactor` used in rational mode
+/// (see `ConversionGraph::set_rational_mode`). Composing and inverting never rounds.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RationalFactor {
+    scale: BigRational,
+    offset: BigRational,
+}
+
+impl RationalFactor {
+    pub fn new(scale: BigRational, offset: BigRational) -> Self {
+        Self { scale, offset }
+    }
+
+    /// The factor that leaves values unchanged.
+    pub fn identity() -> Self {
+        Self::new(BigRational::one(), BigRational::zero())
+    }
+
+    /// The exact factor a `ConversionFactor` was most likely written as: each float is
+    /// read back as the shortest decimal that rounds to it, so `0.3048` gives 3048/10000.
+    pub fn from_factor(factor: &ConversionFactor) -> Self {
+        Self::new(rational_from_f64(factor.scale()), rational_from_f64(factor.offset()))
+    }
+
+    pub fn scale(&self) -> &BigRational {
+        &self.scale
+    }
+
+    pub fn offset(&self) -> &BigRational {
+        &self.offset
+    }
+
+    pub fn apply(&self, value: &BigRational) -> BigRational {
+        value * &self.scale + &self.offset
+    }
+
+    /// The factor undoing this one. The scale must not be zero.
+    pub fn inverse(&self) -> Self {
+        let scale = self.scale.recip();
+        let offset = -(&self.offset * &scale);
+        Self::new(scale, offset)
+    }
+
+    /// The factor applying `self` first, then `next`.
+    pub fn then(&self, next: &RationalFactor) -> Self {
+        Self::new(&next.scale * &self.scale, &next.scale * &self.offset + &next.offset)
+    }
+
+    /// The nearest floating point factor.
+    pub fn to_factor(&self) -> ConversionFactor {
+        ConversionFactor::new(rational_to_f64(&self.scale), rational_to_f64(&self.offset))
+    }
+}
+
+/// Displays the factor as an affine map, e.g. `x * 9/5 + 32` or `x * 0.3048 + 0`.
+impl fmt::Display for RationalFactor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.offset.is_negative() {
+            write!(f, "x * {} - {}", rational_to_string(&self.scale), rational_to_string(&-&self.offset))
+        } else {
+            write!(f, "x * {} + {}", rational_to_string(&self.scale), rational_to_string(&self.offset))
+        }
+    }
+}
+
+/// Parse a decimal (`-459.67`, `1.602176634e-19`) or a fraction of two decimals
+/// (`1200/3937`) into an exact rational. Returns `None` for anything else, including
+/// a zero denominator and exponents beyond ±4096.
+pub fn parse_rational(text: &str) -> Option<BigRational> {
+    match text.split_once('/') {
+        Some((numerator, denominator)) => {
+            let denominator = parse_decimal(denominator.trim())?;
+            if denominator.is_zero() {
+                return None;
+            }
+            Some(parse_decimal(numerator.trim())? / denominator)
+        }
+        None => parse_decimal(text.trim()),
+    }
+}
+
+fn parse_decimal(text: &str) -> Option<BigRational> {
+    let (mantissa, exponent) = match text.split_once(['e', 'E']) {
+        Some((mantissa, exponent)) => (mantissa, exponent.parse::<i32>().ok()?),
+        None => (text, 0),
+    };
+    if !(-MAX_EXPONENT..=MAX_EXPONENT).contains(&exponent) {
+        return None;
+    }
+    let (negative, mantissa) = match mantissa.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, mantissa.strip_prefix('+').unwrap_or(mantissa)),
+    };
+    let (whole, fraction) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+    if whole.is_empty() && fraction.is_empty() || !(whole.chars().chain(fraction.chars())).all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+
+    let digits: BigInt = format!("{}{}", whole, fraction).parse().ok()?;
+    let exponent = exponent.checked_sub(i32::try_from(fraction.len()).ok()?)?;
+    let ten = BigInt::from(10);
+    let value = if exponent >= 0 {
+        BigRational::from_integer(digits * num_traits::pow(ten, exponent as usize))
+    } else {
+        BigRational::new(digits, num_traits::pow(ten, exponent.unsigned_abs() as usize))
+    };
+    Some(if negative { -value } else { value })
+}
+
+/// The shortest decimal that rounds to `value`, as an exact rational.
+/// `value` must be finite.
+pub(crate) fn rational_from_f64(value: f64) -> BigRational {
+    parse_decimal(&format!("{:e}", value)).expect("finite floats format as decimals")
+}
+
+/// The float nearest to `value`.
+pub(crate) fn rational_to_f64(value: &BigRational) -> f64 {
+    value.to_f64().unwrap_or(f64::NAN)
+}
+
+/// Write `value` as a decimal if it has a finite decimal expansion (`0.3048`), and as a
+/// fraction otherwise (`5/9`). `parse_rational` reads the result back exactly.
+pub(crate) fn rational_to_string(value: &BigRational) -> String {
+    let mut denominator = value.denom().clone();
+    let mut places = 0;
+    for factor in [2u32, 5] {
+        let mut count = 0;
+        while (&denominator % factor).is_zero() {
+            denominator /= factor;
+            count += 1;
+        }
+        places = places.max(count);
+    }
+    if !denominator.is_one() {
+        return format!("{}/{}", value.numer(), value.denom());
+    }
+    format_decimal(value, places)
+}
+
+/// Round `value` to `places` decimal places, halves away from zero, and write it out
+/// in full, e.g. `1/3` to 4 places is `0.3333`. At most `MAX_DECIMAL_PLACES` places are
+/// written.
+pub fn format_decimal(value: &BigRational, places: usize) -> String {
+    let places = places.min(MAX_DECIMAL_PLACES);
+    let scale = num_traits::pow(BigInt::from(10), places);
+    let rounded = (value * BigRational::from_integer(scale)).round().to_integer();
+    let digits = rounded.abs().to_string();
+    let sign = if rounded.is_negative() { "-" } else { "" };
+    if places == 0 {
+        return format!("{}{}", sign, digits);
+    }
+    let digits = format!("{:0>width$}", digits, width = places + 1);
+    let (whole, fraction) = digits.split_at(digits.len() - places);
+    format!("{}{}.{}", sign, whole, fraction)
+}
+
+/// Rational mode.
+impl ConversionGraph {
+    /// Get the exact composed conversion factor from one unit to another. In rational
+    /// mode every factor is exact; otherwise each float factor on the path is read back as
+    /// the shortest decimal that rounds to it (see `RationalFactor::from_factor`).
+    ///
+    /// # Error
+    ///
+    /// Error if either of the units do not exist in the graph or no path connects them.
+    pub fn rational_factor_between(&self, from: &str, to: &str) -> Result<RationalFactor, ConversionError> {
+        let hops = self.hops(from, to)?;
+        Ok(hops.iter().fold(RationalFactor::identity(), |total, hop| {
+            match &hop.rational {
+                Some(rational) => total.then(rational),
+                None => total.then(&RationalFactor::from_factor(&hop.factor)),
+            }
+        }))
+    }
+
+    /// Convert an exact value from one unit to another without any rounding.
+    ///
+    /// # Error
+    ///
+    /// Error if either of the units do not exist in the graph or no path connects them.
+    pub fn convert_rational(&self, from: &str, to: &str, value: &BigRational) -> Result<BigRational, ConversionError> {
+        Ok(self.rational_factor_between(from, to)?.apply(value))
+    }
+
+    /// Convert a value written as a decimal or a fraction (see `parse_rational`) exactly,
+    /// then round the result to `places` decimal places.
+    ///
+    /// # Error
+    ///
+    /// Error if the value is not a number, either of the units do not exist in the graph
+    /// or no path connects them.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use conversion_wiz::ConversionGraph;
+    ///
+    /// let mut graph = ConversionGraph::new();
+    /// graph.set_rational_mode(true);
+    /// graph.add_unit("foot", vec!["ft"], false).unwrap();
+    /// graph.add_unit("meter", vec!["m"], false).unwrap();
+    /// graph.add_edge("ft", "m", 0.3048, 0.0).unwrap();
+    /// assert_eq!(graph.convert_decimal("m", "ft", "1", 20).unwrap(), "3.28083989501312335958");
+    /// ```
+    pub fn convert_decimal(&self, from: &str, to: &str, value: &str, places: usize) -> Result<String, ConversionError> {
+        let value = parse_rational(value).ok_or_else(|| ConversionError::InvalidNumber(value.to_string()))?;
+        Ok(format_decimal(&self.convert_rational(from, to, &value)?, places))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ratio(numerator: i64, denominator: i64) -> BigRational {
+        BigRational::new(numerator.into(), denominator.into())
+    }
+
+    #[test]
+    fn test_parse_rational() {
+        assert_eq!(parse_rational("0.3048"), Some(ratio(381, 1250)));
+        assert_eq!(parse_rational("-459.67"), Some(ratio(-45967, 100)));
+        assert_eq!(parse_rational("1.5e3"), Some(ratio(1500, 1)));
+        assert_eq!(parse_rational("25E-2"), Some(ratio(1, 4)));
+        assert_eq!(parse_rational(".5"), Some(ratio(1, 2)));
+        assert_eq!(parse_rational("1200/3937"), Some(ratio(1200, 3937)));
+        assert_eq!(parse_rational(" 1 / 0.3 "), Some(ratio(10, 3)));
+        for invalid in ["", ".", "1/0", "abc", "1.2.3", "1e", "--1", "0x10"] {
+            assert_eq!(parse_rational(invalid), None, "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_parse_rational_bounds_exponents() {
+        assert_eq!(parse_rational("1e4096").unwrap().numer().to_string().len(), 4097);
+        assert!(parse_rational("1e-4096").is_some());
+        for huge in ["1e4097", "1e-4097", "1e999999999", "1/1e999999999", "1e99999999999"] {
+            assert_eq!(parse_rational(huge), None, "{}", huge);
+        }
+        assert_eq!(format_decimal(&ratio(1, 3), usize::MAX).len(), MAX_DECIMAL_PLACES + 2);
+    }
+
+    #[test]
+    fn test_rational_from_f64_is_shortest_decimal() {
+        assert_eq!(rational_from_f64(0.001), ratio(1, 1000));
+        assert_eq!(rational_from_f64(1e3), ratio(1000, 1));
+        assert_eq!(rational_from_f64(-273.15), ratio(-27315, 100));
+        assert_eq!(rational_from_f64(0.0), ratio(0, 1));
+        assert_eq!(rational_to_f64(&ratio(1, 3)), 1.0 / 3.0);
+    }
+
+    #[test]
+    fn test_rational_to_string_and_format_decimal() {
+        assert_eq!(rational_to_string(&ratio(381, 1250)), "0.3048");
+        assert_eq!(rational_to_string(&ratio(-5, 1)), "-5");
+        assert_eq!(rational_to_string(&ratio(5, 9)), "5/9");
+        assert_eq!(format_decimal(&ratio(1, 3), 4), "0.3333");
+        assert_eq!(format_decimal(&ratio(2, 3), 0), "1");
+        assert_eq!(format_decimal(&ratio(-1, 8), 2), "-0.13");
+        assert_eq!(format_decimal(&ratio(1, 200), 1), "0.0");
+        assert_eq!(format_decimal(&ratio(12345, 100), 3), "123.450");
+    }
+
+    #[test]
+    fn test_rational_factor_algebra() {
+        let fahrenheit_to_celsius = RationalFactor::new(ratio(5, 9), ratio(-160, 9));
+        let celsius_to_fahrenheit = fahrenheit_to_celsius.inverse();
+        assert_eq!(celsius_to_fahrenheit, RationalFactor::new(ratio(9, 5), ratio(32, 1)));
+        assert_eq!(fahrenheit_to_celsius.then(&celsius_to_fahrenheit), RationalFactor::identity());
+        assert_eq!(celsius_to_fahrenheit.apply(&ratio(100, 1)), ratio(212, 1));
+        assert_eq!(fahrenheit_to_celsius.to_string(), "x * 5/9 - 160/9");
+    }
+
+    #[test]
+    fn test_rational_mode_round_trip_is_exact() {
+        let mut graph = ConversionGraph::new();
+        graph.set_rational_mode(true);
+        let _ = graph.add_unit("meter", vec!["m"], false);
+        let _ = graph.add_unit("foot", vec!["ft"], false);
+        let _ = graph.add_unit("inch", vec!["in"], false);
+        let _ = graph.add_edge("ft", "m", 0.3048, 0.0);
+        let _ = graph.add_edge("ft", "in", 12.0, 0.0);
+        let _ = graph.add_edge("in", "m", 0.0254, 0.0);
+
+        // meter -> foot -> inch -> meter
+        let around = graph.rational_factor_between("m", "ft").unwrap()
+            .then(&graph.rational_factor_between("ft", "in").unwrap())
+            .then(&graph.rational_factor_between("in", "m").unwrap());
+        assert_eq!(around, RationalFactor::identity());
+
+        let mut exact = ConversionGraph::new();
+        exact.set_rational_mode(true);
+        let _ = exact.add_unit("meter", vec!["m"], false);
+        let _ = exact.add_unit("kilometer", vec!["km"], false);
+        let _ = exact.add_edge("m", "km", 0.001, 0.0);
+        assert_eq!(exact.rational_factor_between("km", "m").unwrap().scale(), &ratio(1000, 1));
+        assert_eq!(exact.convert("km", "m", 1.0).unwrap(), 1000.0);
+        assert_eq!(exact.convert_decimal("km", "m", "1/3", 3).unwrap(), "333.333");
+        assert!(matches!(exact.convert_decimal("km", "m", "x", 3), Err(ConversionError::InvalidNumber(_))));
+    }
+}
@@ -0,0 +1,145 @@
+//! Reachability: which units a unit can be converted to, and the connected components
+//! of the graph.
+
+use crate::{ConversionError, ConversionGraph, Unit};
+
+/// Reachability.
+impl ConversionGraph {
+    /// The visible units `unit` can be converted to, sorted by name, without the unit
+    /// itself. Intermediate units are walked through but not listed. A prefixed alias
+    /// such as `km` reaches the same units as its base unit, and the base unit itself.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ConversionError::UnitNotFound` if the unit does not exist.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use conversion_wiz::ConversionGraph;
+    ///
+    /// let mut graph = ConversionGraph::new();
+    /// graph.add_unit("meter", vec!["m"], false).unwrap();
+    /// graph.add_unit("foot", vec!["ft"], false).unwrap();
+    /// graph.add_unit("gram", vec!["g"], false).unwrap();
+    /// graph.add_edge("ft", "m", 0.3048, 0.0).unwrap();
+    /// let reachable: Vec<&str> = graph.reachable_from("m").unwrap().iter().map(|unit| unit.name()).collect();
+    /// assert_eq!(reachable, vec!["foot"]);
+    /// ```
+    pub fn reachable_from(&self, unit: &str) -> Result<Vec<&Unit>, ConversionError> {
+        let resolved = self.resolve(unit)?;
+        let mut reachable: Vec<&Unit> = self.search(resolved.name, None)
+            .order
+            .into_iter()
+            .map(|name| &self.name_to_units[name])
+            .filter(|reached| !reached.intermediate() && (reached.name() != resolved.name || resolved.prefix.is_some()))
+            .collect();
+        reachable.sort_by(|a, b| a.name().cmp(b.name()));
+        Ok(reachable)
+    }
+
+    /// The connected components of the graph: groups of visible units that can all be
+    /// converted to each other, each sorted by name and ordered by their first unit.
+    /// Intermediate units join components but are not listed, and a component of only
+    /// intermediate units is left out.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use conversion_wiz::ConversionGraph;
+    ///
+    /// let mut graph = ConversionGraph::new();
+    /// graph.add_unit("meter", vec!["m"], false).unwrap();
+    /// graph.add_unit("foot", vec!["ft"], false).unwrap();
+    /// graph.add_unit("gram", vec!["g"], false).unwrap();
+    /// graph.add_edge("ft", "m", 0.3048, 0.0).unwrap();
+    /// let components: Vec<Vec<&str>> = graph.components()
+    ///     .iter()
+    ///     .map(|component| component.iter().map(|unit| unit.name()).collect())
+    ///     .collect();
+    /// assert_eq!(components, vec![vec!["foot", "meter"], vec!["gram"]]);
+    /// ```
+    pub fn components(&self) -> Vec<Vec<&Unit>> {
+        let mut components: Vec<Vec<&Unit>> = self.spanning_forest()
+            .into_iter()
+            .map(|tree| {
+                let mut units: Vec<&Unit> = tree.order
+                    .into_iter()
+                    .map(|name| &self.name_to_units[name])
+                    .filter(|unit| !unit.intermediate())
+                    .collect();
+                units.sort_by(|a, b| a.name().cmp(b.name()));
+                units
+            })
+            .filter(|units| !units.is_empty())
+            .collect();
+        components.sort_by(|a, b| a[0].name().cmp(b[0].name()));
+        components
+    }
+
+    /// Whether a value in `from` can be converted to `to`, i.e. both units exist and are
+    /// in the same component. Cheaper than `factor_between`, as no factor is composed.
+    pub fn is_convertible(&self, from: &str, to: &str) -> bool {
+        let (Ok(from), Ok(to)) = (self.resolve(from), self.resolve(to)) else {
+            return false;
+        };
+        from.name == to.name || self.search(from.name, Some(to.name)).order.last() == Some(&to.name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(units: &[&Unit]) -> Vec<String> {
+        units.iter().map(|unit| unit.name().to_string()).collect()
+    }
+
+    fn sample_graph() -> ConversionGraph {
+        let mut graph = ConversionGraph::new();
+        let _ = graph.add_prefixable_unit("meter", vec!["m"], false);
+        let _ = graph.add_unit("foot", vec!["ft"], false);
+        let _ = graph.add_unit("Celsius", vec!["C"], false);
+        let _ = graph.add_unit("Fahrenheit", vec!["F"], false);
+        let _ = graph.add_unit("Kelvin", vec!["K"], false);
+        let _ = graph.add_unit("k1", vec![], true);
+        let _ = graph.add_unit("lonely", vec![], true);
+        let _ = graph.add_unit("gram", vec!["g"], false);
+        let _ = graph.add_edge("ft", "m", 0.3048, 0.0);
+        let _ = graph.add_edge("C", "k1", 1.0, 273.15);
+        let _ = graph.add_edge("k1", "K", 1.0, 0.0);
+        let _ = graph.add_edge("C", "F", 1.8, 32.0);
+        graph
+    }
+
+    #[test]
+    fn test_reachable_from() {
+        let graph = sample_graph();
+        assert_eq!(names(&graph.reachable_from("C").unwrap()), vec!["Fahrenheit", "Kelvin"]);
+        assert_eq!(names(&graph.reachable_from("Kelvin").unwrap()), vec!["Celsius", "Fahrenheit"]);
+        assert_eq!(names(&graph.reachable_from("km").unwrap()), vec!["foot", "meter"]);
+        assert!(graph.reachable_from("g").unwrap().is_empty());
+        assert!(matches!(graph.reachable_from("X"), Err(ConversionError::UnitNotFound { .. })));
+    }
+
+    #[test]
+    fn test_components() {
+        let graph = sample_graph();
+        let components: Vec<Vec<String>> = graph.components().iter().map(|component| names(component)).collect();
+        assert_eq!(components, vec![
+            vec!["Celsius", "Fahrenheit", "Kelvin"],
+            vec!["foot", "meter"],
+            vec!["gram"],
+        ]);
+    }
+
+    #[test]
+    fn test_is_convertible() {
+        let graph = sample_graph();
+        assert!(graph.is_convertible("F", "K"));
+        assert!(graph.is_convertible("km", "ft"));
+        assert!(graph.is_convertible("g", "g"));
+        assert!(!graph.is_convertible("m", "K"));
+        assert!(!graph.is_convertible("m", "X"));
+    }
+}
@@ -0,0 +1,158 @@
+//! The standard unit catalog compiled into the crate.
+
+use crate::{Config, ConfigFormat, ConversionGraph};
+
+/// The catalog itself, in the same schema as `data.json`.
+///
+/// Values follow the SI Brochure (BIPM, 9th edition) and NIST SP 811 (2008), Appendix B.
+/// Conversions marked `exact` are exact by definition, e.g. the international foot
+/// (0.3048 m), pound (0.45359237 kg), US gallon (231 in³), thermochemical calorie
+/// (4.184 J), mechanical horsepower (550 ft·lbf/s, written out in full) and, since
+/// 2019, the electronvolt. The others are the values NIST lists: the international
+/// table BTU and mmHg.
+const STANDARD_CATALOG: &str = include_str!("standard.json");
+
+impl Config {
+    /// The standard unit catalog: SI base and derived units, imperial and US customary
+    /// units, temperature scales, time, data sizes, pressure and energy.
+    pub fn standard() -> Config {
+        Config::parse(STANDARD_CATALOG, ConfigFormat::Json).expect("the standard catalog is a valid config")
+    }
+}
+
+/// The standard unit catalog.
+impl ConversionGraph {
+    /// A graph with the standard unit catalog (see `Config::standard`). Units that take
+    /// SI prefixes (meter, gram, second, liter, joule, ...) also accept them, so `km`,
+    /// `mg` or `kWh` work too; bit and byte also take binary prefixes (`KiB`, `GiB`).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use conversion_wiz::ConversionGraph;
+    ///
+    /// let graph = ConversionGraph::with_standard_units();
+    /// assert!((graph.convert("mi", "km", 1.0).unwrap() - 1.609344).abs() < 1e-12);
+    /// assert!((graph.convert("degF", "degC", 212.0).unwrap() - 100.0).abs() < 1e-9);
+    /// ```
+    pub fn with_standard_units() -> Self {
+        ConversionGraph::from_config(&Config::standard()).expect("the standard catalog loads")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_rational, ConfigValidator, Severity};
+    use approx::assert_relative_eq;
+
+    const REL_TOL: f64 = 1e-9;
+
+    fn check(graph: &ConversionGraph, from: &str, to: &str, value: f64, expected: f64) {
+        let result = graph.convert(from, to, value).unwrap_or_else(|e| panic!("{} -> {}: {}", from, to, e));
+        assert_relative_eq!(result, expected, max_relative = REL_TOL);
+    }
+
+    #[test]
+    fn test_standard_catalog_is_valid() {
+        let config = Config::standard();
+        let errors: Vec<_> = ConfigValidator::new().validate(&config)
+            .into_iter()
+            .filter(|d| d.severity() == Severity::Error)
+            .collect();
+        assert!(errors.is_empty(), "{:?}", errors);
+        let graph = ConversionGraph::from_config(&config).unwrap();
+        assert!(graph.check_consistency(REL_TOL).is_empty());
+        assert!(graph.units().iter().all(|unit| unit.dimension().is_some()));
+    }
+
+    #[test]
+    fn test_standard_exact_conversions() {
+        // the only conversions that are not definitions
+        let listed = [("BTU", "J"), ("mmHg", "Pa")];
+        for conversion in Config::standard().conversions {
+            let path = (conversion.from.as_str(), conversion.to.as_str());
+            assert_eq!(conversion.exact, !listed.contains(&path), "{} -> {}", path.0, path.1);
+        }
+        let mut graph = ConversionGraph::new();
+        graph.set_rational_mode(true);
+        graph.load_config(&Config::standard()).unwrap();
+        // 550 ft·lbf/s
+        let hp = ["550", "0.3048", "4.4482216152605"].iter().map(|text| parse_rational(text).unwrap()).product();
+        assert_eq!(graph.rational_factor_between("hp", "W").unwrap().scale(), &hp);
+    }
+
+    #[test]
+    fn test_standard_length_and_mass() {
+        let graph = ConversionGraph::with_standard_units();
+        check(&graph, "in", "cm", 1.0, 2.54);
+        check(&graph, "mi", "ft", 1.0, 5280.0);
+        check(&graph, "yd", "in", 1.0, 36.0);
+        check(&graph, "nmi", "km", 1.0, 1.852);
+        check(&graph, "ftUS", "m", 3937.0, 1200.0);
+        check(&graph, "lb", "g", 1.0, 453.59237);
+        check(&graph, "oz", "g", 1.0, 28.349523125);
+        check(&graph, "gr", "mg", 1.0, 64.79891);
+        check(&graph, "ton", "t", 1.0, 0.90718474);
+        check(&graph, "long ton", "kg", 1.0, 1016.0469088);
+        check(&graph, "st", "lb", 1.0, 14.0);
+    }
+
+    #[test]
+    fn test_standard_time_and_temperature() {
+        let graph = ConversionGraph::with_standard_units();
+        check(&graph, "yr", "s", 1.0, 31_557_600.0);
+        check(&graph, "wk", "min", 1.0, 10_080.0);
+        check(&graph, "h", "ms", 1.0, 3.6e6);
+        check(&graph, "degC", "K", 0.0, 273.15);
+        check(&graph, "degF", "degC", 50.0, 10.0);
+        check(&graph, "degF", "degC", -40.0, -40.0);
+        check(&graph, "degF", "K", 212.0, 373.15);
+        check(&graph, "degR", "degF", 0.0, -459.67);
+    }
+
+    #[test]
+    fn test_standard_volume_and_data() {
+        let graph = ConversionGraph::with_standard_units();
+        check(&graph, "gal", "m3", 1.0, 231.0 * 0.0254_f64.powi(3));
+        check(&graph, "gal", "qt", 1.0, 4.0);
+        check(&graph, "cup", "floz", 1.0, 8.0);
+        check(&graph, "floz", "mL", 1.0, 29.5735295625);
+        check(&graph, "impgal", "L", 1.0, 4.54609);
+        check(&graph, "m3", "L", 1.0, 1000.0);
+        check(&graph, "B", "b", 1.0, 8.0);
+        check(&graph, "KiB", "B", 1.0, 1024.0);
+        check(&graph, "GB", "MiB", 1.0, 1e9 / 1_048_576.0);
+        check(&graph, "Mb", "kB", 1.0, 125.0);
+    }
+
+    #[test]
+    fn test_standard_derived_units() {
+        let graph = ConversionGraph::with_standard_units();
+        check(&graph, "lbf", "N", 1.0, 4.4482216152605);
+        check(&graph, "dyn", "mN", 1.0, 0.01);
+        check(&graph, "hp", "W", 1.0, 550.0 * 0.3048 * 4.4482216152605);
+        check(&graph, "atm", "kPa", 1.0, 101.325);
+        check(&graph, "atm", "Torr", 1.0, 760.0);
+        check(&graph, "psi", "Pa", 1.0, 6894.757293168361);
+        check(&graph, "mmHg", "Pa", 1.0, 133.322387415);
+        check(&graph, "bar", "mbar", 1.0, 1000.0);
+        check(&graph, "kcal", "J", 1.0, 4184.0);
+        check(&graph, "kWh", "MJ", 1.0, 3.6);
+        check(&graph, "BTU", "J", 1.0, 1055.05585262);
+        check(&graph, "eV", "J", 1.0, 1.602176634e-19);
+        check(&graph, "erg", "J", 1.0, 1e-7);
+        check(&graph, "kHz", "Hz", 1.0, 1000.0);
+        check(&graph, "mA", "A", 1.0, 0.001);
+    }
+
+    #[test]
+    fn test_standard_rational_mode() {
+        let mut graph = ConversionGraph::new();
+        graph.set_rational_mode(true);
+        graph.load_config(&Config::standard()).unwrap();
+        assert_eq!(graph.convert_decimal("degF", "degC", "98.6", 12).unwrap(), "37.000000000000");
+        assert_eq!(graph.convert_decimal("psi", "Pa", "1", 6).unwrap(), "6894.757293");
+        assert_eq!(graph.convert_decimal("ftUS", "m", "3937", 12).unwrap(), "1200.000000000000");
+    }
+}
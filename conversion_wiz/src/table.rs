@@ -0,0 +1,107 @@
+//! Converting one value to every unit it can be converted to.
+
+use crate::rational::{rational_from_f64, rational_to_f64};
+use crate::{ConversionError, ConversionGraph, RationalFactor, Unit};
+
+/// Conversion to every unit.
+impl ConversionGraph {
+    /// Convert a value to every visible unit it can be converted to (the units of
+    /// `reachable_from`), sorted by unit name. The whole component is walked once, and
+    /// each result is the same as `convert` to that unit gives.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ConversionError::UnitNotFound` if the unit does not exist.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use conversion_wiz::ConversionGraph;
+    ///
+    /// let mut graph = ConversionGraph::new();
+    /// graph.add_prefixable_unit("meter", vec!["m"], false).unwrap();
+    /// graph.add_unit("foot", vec!["ft"], false).unwrap();
+    /// graph.add_edge("ft", "m", 0.3048, 0.0).unwrap();
+    /// let results: Vec<(&str, f64)> = graph.convert_to_all("km", 1.0).unwrap()
+    ///     .into_iter()
+    ///     .map(|(unit, value)| (unit.name(), value))
+    ///     .collect();
+    /// assert_eq!(results, vec![("foot", 1000.0 / 0.3048), ("meter", 1000.0)]);
+    /// ```
+    pub fn convert_to_all(&self, from: &str, value: f64) -> Result<Vec<(&Unit, f64)>, ConversionError> {
+        let from_unit = self.resolve(from)?;
+        let tree = self.search(from_unit.name, None);
+        // compose the same factors, in the same order, as `convert` does along each path
+        let results: Vec<(&str, f64)> = if self.rational_mode && value.is_finite() {
+            let value = rational_from_f64(value);
+            tree.compose(self, from_unit.prefix_rational(), |factor, edge| match &edge.rational {
+                Some(rational) => factor.then(rational),
+                None => factor.then(&RationalFactor::from_factor(&edge.factor)),
+            })
+            .into_iter()
+            .map(|(name, factor)| (name, rational_to_f64(&factor.apply(&value))))
+            .collect()
+        } else {
+            tree.compose(self, from_unit.prefix_factor(), |factor, edge| factor.then(&edge.factor))
+                .into_iter()
+                .map(|(name, factor)| (name, factor.apply(value)))
+                .collect()
+        };
+
+        let mut results: Vec<(&Unit, f64)> = results
+            .into_iter()
+            .map(|(name, value)| (&self.name_to_units[name], value))
+            .filter(|(unit, _)| !unit.intermediate() && (unit.name() != from_unit.name || from_unit.prefix.is_some()))
+            .collect();
+        results.sort_by(|(a, _), (b, _)| a.name().cmp(b.name()));
+        Ok(results)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Config;
+
+    #[test]
+    fn test_convert_to_all_matches_convert() {
+        let graph = ConversionGraph::with_standard_units();
+        for from in ["km", "mi", "degF", "kWh", "GiB"] {
+            let results = graph.convert_to_all(from, 12.5).unwrap();
+            let reachable: Vec<&str> = graph.reachable_from(from).unwrap().iter().map(|unit| unit.name()).collect();
+            assert_eq!(results.iter().map(|(unit, _)| unit.name()).collect::<Vec<_>>(), reachable);
+            for (unit, value) in results {
+                assert_eq!(value, graph.convert(from, unit.name(), 12.5).unwrap(), "{} -> {}", from, unit.name());
+            }
+        }
+    }
+
+    #[test]
+    fn test_convert_to_all_rational_mode() {
+        let mut graph = ConversionGraph::new();
+        graph.set_rational_mode(true);
+        graph.load_config(&Config::standard()).unwrap();
+        let results = graph.convert_to_all("degF", 98.6).unwrap();
+        let celsius = results.iter().find(|(unit, _)| unit.name() == "Celsius").unwrap();
+        assert_eq!(celsius.1, 37.0);
+        for (unit, value) in results {
+            assert_eq!(value, graph.convert("degF", unit.name(), 98.6).unwrap(), "degF -> {}", unit.name());
+        }
+    }
+
+    #[test]
+    fn test_convert_to_all_skips_intermediate_units() {
+        let mut graph = ConversionGraph::new();
+        let _ = graph.add_unit("Celsius", vec!["C"], false);
+        let _ = graph.add_unit("k1", vec![], true);
+        let _ = graph.add_unit("Kelvin", vec!["K"], false);
+        let _ = graph.add_unit("gram", vec!["g"], false);
+        let _ = graph.add_edge("C", "k1", 1.0, 273.15);
+        let _ = graph.add_edge("k1", "K", 1.0, 0.0);
+        let results = graph.convert_to_all("C", 100.0).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!((results[0].0.name(), results[0].1), ("Kelvin", 373.15));
+        assert!(graph.convert_to_all("g", 1.0).unwrap().is_empty());
+        assert!(matches!(graph.convert_to_all("X", 1.0), Err(ConversionError::UnitNotFound { .. })));
+    }
+}
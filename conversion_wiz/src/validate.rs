@@ -0,0 +1,466 @@
+//! Validation of config files, reporting every problem instead of the first one.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use crate::config::CONFIG_LOOKUP;
+use crate::{Config, ConfigFormat, ConversionError, ConversionFactor, ConversionGraph, DuplicateEdgePolicy, Unit, UNCATEGORIZED};
+
+/// `Severity` enum to tell problems that stop a config from loading from suspicious
+/// entries that load but probably do not do what was intended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+/// `Diagnostic` struct to represent one problem of a config, with the JSON path of
+/// the offending entry, e.g. `$.units[3].aliases[1]`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    severity: Severity,
+    path: String,
+    message: String,
+}
+
+impl Diagnostic {
+    fn error(path: impl Into<String>, message: impl Into<String>) -> Self {
+        Self { severity: Severity::Error, path: path.into(), message: message.into() }
+    }
+
+    fn warning(path: impl Into<String>, message: impl Into<String>) -> Self {
+        Self { severity: Severity::Warning, path: path.into(), message: message.into() }
+    }
+
+    pub fn severity(&self) -> Severity {
+        self.severity
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+/// Displays the diagnostic as `error at $.units[1].name: ...`.
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        };
+        write!(f, "{} at {}: {}", severity, self.path, self.message)
+    }
+}
+
+/// `ConfigValidator` struct to check a whole config and collect all of its problems.
+///
+/// Entries are loaded the way `ConversionGraph::load_config` loads them, with units
+/// referred to by exact alias whatever the graph's `UnitLookup`, so errors are exactly
+/// the entries it would reject: empty or duplicate names and aliases, invalid
+/// dimensions, conversions between unknown units, zero factors and conversions between
+/// different dimensions. Warnings are entries that load but are likely mistakes:
+/// conversions replacing an earlier conversion between the same units, conversions that
+/// disagree with the path already connecting their units (so going around the cycle
+/// does not give back the same value), units without any conversion, and groups of
+/// units of the same category that no conversion connects (see `components`).
+///
+/// Repeated conversions are judged by the validator's `DuplicateEdgePolicy`, like the
+/// graph would: errors under `Error`, warnings under `Warn` and `Replace`, and accepted
+/// under `Merge`.
+///
+/// Invalid entries are skipped and validation carries on, so one bad entry does not hide
+/// the problems after it.
+#[derive(Debug, Clone, Copy)]
+pub struct ConfigValidator {
+    tolerance: f64,
+    duplicate_edge_policy: DuplicateEdgePolicy,
+}
+
+impl Default for ConfigValidator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ConfigValidator {
+    /// A validator comparing factors with a relative tolerance of 1e-9, and the default
+    /// `DuplicateEdgePolicy`.
+    pub fn new() -> Self {
+        Self { tolerance: 1e-9, duplicate_edge_policy: DuplicateEdgePolicy::default() }
+    }
+
+    /// How far factors around a cycle may drift before they are reported as disagreeing.
+    /// See `ConversionFactor::approx_eq`.
+    pub fn with_tolerance(mut self, tolerance: f64) -> Self {
+        self.tolerance = tolerance;
+        self
+    }
+
+    /// Validate repeated conversions against the given policy.
+    pub fn with_duplicate_edge_policy(mut self, policy: DuplicateEdgePolicy) -> Self {
+        self.duplicate_edge_policy = policy;
+        self
+    }
+
+    /// Parse and validate a JSON config. A config that is not valid JSON, or does not
+    /// have the expected shape, gives a single error at `$`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use conversion_wiz::{ConfigValidator, Severity};
+    ///
+    /// let diagnostics = ConfigValidator::new().validate_json(r#"{
+    ///     "units": [
+    ///         { "name": "meter", "aliases": ["m"], "intermediate": false },
+    ///         { "name": "mile", "aliases": ["m"], "intermediate": false }
+    ///     ],
+    ///     "conversions_scale": [{ "from": "m", "to": "yard", "factor": 1.09361 }]
+    /// }"#);
+    /// assert_eq!(diagnostics.len(), 4);
+    /// assert_eq!(diagnostics[0].path(), "$.units[1].aliases[0]");
+    /// assert_eq!(diagnostics[1].path(), "$.conversions_scale[0].to");
+    /// // neither meter nor mile has a conversion
+    /// assert_eq!(diagnostics[2].severity(), Severity::Warning);
+    /// ```
+    pub fn validate_json(&self, json: &str) -> Vec<Diagnostic> {
+        self.validate_str(json, ConfigFormat::Json)
+    }
+
+    /// Parse and validate a config in any supported format. Paths are reported in the
+    /// same JSON path notation whatever the format, since all formats share the schema.
+    pub fn validate_str(&self, text: &str, format: ConfigFormat) -> Vec<Diagnostic> {
+        match Config::parse(text, format) {
+            Ok(config) => self.validate(&config),
+            Err(e) => vec![Diagnostic::error("$", e.to_string())],
+        }
+    }
+
+    /// Validate a config, returning its problems in the order of the entries.
+    pub fn validate(&self, config: &Config) -> Vec<Diagnostic> {
+        let mut diagnostics = Vec::new();
+        let mut graph = ConversionGraph::new();
+        graph.set_duplicate_edge_policy(self.duplicate_edge_policy);
+        graph.set_unit_lookup(CONFIG_LOOKUP);
+        let mut unit_paths: HashMap<String, String> = HashMap::new();
+        let mut alias_paths: HashMap<String, String> = HashMap::new();
+
+        for (i, unit_config) in config.units.iter().enumerate() {
+            let path = format!("$.units[{}]", i);
+            let mut entry = unit_config.clone();
+            // index in the config of each alias still in the entry
+            let mut indices: Vec<usize> = (0..entry.aliases.len()).collect();
+            // problems of the entry, ranked by field: name, aliases, dimension
+            let mut problems: Vec<(usize, Diagnostic)> = Vec::new();
+
+            // load the entry as `from_config` does, leaving out each invalid alias or
+            // dimension and trying again, so later entries can still refer to the unit
+            let inserted = loop {
+                let e = match entry.to_unit().and_then(|unit| graph.insert_unit(unit)) {
+                    Ok(()) => break true,
+                    Err(e) => e,
+                };
+                let alias = match &e {
+                    ConversionError::EmptyAlias => entry.aliases.iter().position(String::is_empty),
+                    ConversionError::DuplicateAlias(alias) if *alias != entry.name => {
+                        entry.aliases.iter().position(|other| other == alias)
+                    }
+                    _ => None,
+                };
+                if let Some(k) = alias {
+                    let message = match &e {
+                        ConversionError::DuplicateAlias(alias) => format!("Alias {} is already used at {}", alias, alias_paths[alias]),
+                        e => e.to_string(),
+                    };
+                    problems.push((1 + indices[k], Diagnostic::error(format!("{}.aliases[{}]", path, indices[k]), message)));
+                    entry.aliases.remove(k);
+                    indices.remove(k);
+                    continue;
+                }
+
+                let name_path = format!("{}.name", path);
+                match e {
+                    ConversionError::InvalidDimension(_) => {
+                        problems.push((usize::MAX, Diagnostic::error(format!("{}.dimension", path), e.to_string())));
+                        entry.dimension = None;
+                        continue;
+                    }
+                    ConversionError::DuplicateUnit(name) => {
+                        let message = format!("Unit {} is already defined at {}", name, unit_paths[&name]);
+                        problems.push((0, Diagnostic::error(name_path, message)));
+                    }
+                    // the name is an alias of the unit too
+                    ConversionError::DuplicateAlias(name) => {
+                        let message = format!("Name {} is already used as an alias at {}", name, alias_paths[&name]);
+                        problems.push((0, Diagnostic::error(name_path, message)));
+                    }
+                    e => problems.push((0, Diagnostic::error(name_path, e.to_string()))),
+                }
+                break false;
+            };
+
+            if inserted {
+                for (alias, j) in entry.aliases.iter().zip(&indices) {
+                    alias_paths.entry(alias.clone()).or_insert_with(|| format!("{}.aliases[{}]", path, j));
+                }
+                alias_paths.entry(entry.name.clone()).or_insert_with(|| format!("{}.name", path));
+                unit_paths.insert(entry.name, path);
+            }
+            problems.sort_by_key(|(rank, _)| *rank);
+            diagnostics.extend(problems.into_iter().map(|(_, diagnostic)| diagnostic));
+        }
+
+        // declared conversions by the (sorted) names of the units they connect
+        let mut declared: HashMap<(String, String), String> = HashMap::new();
+        for edge in config.edges() {
+            let key = match (graph.resolve(edge.from), graph.resolve(edge.to)) {
+                (Ok(from), Ok(to)) if from.name <= to.name => Some((from.name.to_string(), to.name.to_string())),
+                (Ok(from), Ok(to)) => Some((to.name.to_string(), from.name.to_string())),
+                _ => None,
+            };
+            let previous = key.as_ref().and_then(|(from, to)| {
+                let previous = declared.get(&(from.clone(), to.clone()))?;
+                Some(format!("the conversion between {} and {} declared at {}", from, to, previous))
+            });
+            // the path the conversion closes a cycle with, taken before it is added
+            let existing = match previous {
+                Some(_) => None,
+                None => graph.factor_between(edge.from, edge.to).ok().map(|factor| {
+                    let path = graph.path(edge.from, edge.to).map(|path| path.join(" -> ")).unwrap_or_default();
+                    (factor, path)
+                }),
+            };
+
+            if let Err(e) = graph.load_edge(&edge) {
+                let located = |field: &str| format!("{}.{}", edge.path, field);
+                match e {
+                    ConversionError::ConversionRateZero => diagnostics.push(Diagnostic::error(located("factor"), e.to_string())),
+                    ConversionError::UnitNotFound { .. } => {
+                        for (field, alias) in [("from", edge.from), ("to", edge.to)] {
+                            if !graph.contains_unit(alias) {
+                                diagnostics.push(Diagnostic::error(located(field), ConversionError::unit_not_found(alias).to_string()));
+                            }
+                        }
+                    }
+                    e @ ConversionError::DuplicateEdge(..) => {
+                        let message = previous.map_or_else(|| e.to_string(), |pair| format!("Repeats {}", pair));
+                        diagnostics.push(Diagnostic::error(edge.path, message));
+                    }
+                    e => diagnostics.push(Diagnostic::error(edge.path, e.to_string())),
+                }
+                continue;
+            }
+
+            if let (Some(pair), DuplicateEdgePolicy::Warn | DuplicateEdgePolicy::Replace) = (previous, self.duplicate_edge_policy) {
+                diagnostics.push(Diagnostic::warning(edge.path.clone(), format!("Replaces {}", pair)));
+            }
+            if let Some((existing, path)) = existing {
+                let factor = ConversionFactor::new(edge.factor.value(), edge.offset.value());
+                if !existing.approx_eq(&factor, self.tolerance) {
+                    diagnostics.push(Diagnostic::warning(
+                        edge.path.clone(),
+                        format!("Conversion {} disagrees with the existing path {} ({})", factor, path, existing),
+                    ));
+                }
+            }
+            declared.insert(key.expect("a loaded conversion has known units"), edge.path);
+        }
+        graph.take_warnings();
+
+        let connected: HashSet<&str> = graph.edges.iter()
+            .filter(|(_, edges)| !edges.is_empty())
+            .map(|(name, _)| name.as_str())
+            .collect();
+        for (i, unit_config) in config.units.iter().enumerate() {
+            let defined_here = unit_paths.get(&unit_config.name) == Some(&format!("$.units[{}]", i));
+            if defined_here && !connected.contains(unit_config.name.as_str()) {
+                diagnostics.push(Diagnostic::warning(
+                    format!("$.units[{}]", i),
+                    format!("Unit {} has no conversions to any other unit", unit_config.name),
+                ));
+            }
+        }
+
+        // groups of units of the same category that cannot be converted to each other;
+        // lone units are already reported above
+        let components: Vec<Vec<&Unit>> = graph.components().into_iter().filter(|units| units.len() > 1).collect();
+        let category = |unit: &Unit| unit.category().unwrap_or(UNCATEGORIZED).to_string();
+        let names = |units: &[&Unit]| units.iter().map(|unit| unit.name()).collect::<Vec<_>>().join(", ");
+        for (k, component) in components.iter().enumerate() {
+            let disconnected = component.iter().map(|unit| category(unit)).find_map(|shared| {
+                components[..k].iter()
+                    .find(|earlier| earlier.iter().any(|unit| category(unit) == shared))
+                    .map(|earlier| (shared, earlier))
+            });
+            if let Some((shared, earlier)) = disconnected {
+                diagnostics.push(Diagnostic::warning(
+                    unit_paths[component[0].name()].clone(),
+                    format!("Units {} have no conversions to {}, although they share the category {}", names(component), names(earlier), shared),
+                ));
+            }
+        }
+        diagnostics
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::UnitLookup;
+
+    fn paths(diagnostics: &[Diagnostic]) -> Vec<(Severity, &str)> {
+        diagnostics.iter().map(|d| (d.severity(), d.path())).collect()
+    }
+
+    #[test]
+    fn test_validate_clean_config() {
+        let diagnostics = ConfigValidator::new().validate_json(r#"{
+            "units": [
+                { "name": "Celsius", "aliases": ["C"], "intermediate": false, "dimension": "temperature" },
+                { "name": "Fahrenheit", "aliases": ["F"], "intermediate": false, "dimension": "temperature" }
+            ],
+            "conversions": [{ "from": "C", "to": "F", "factor": 1.8, "offset": 32.0 }]
+        }"#);
+        assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+    }
+
+    #[test]
+    fn test_validate_collects_every_error() {
+        let diagnostics = ConfigValidator::new().validate_json(r#"{
+            "units": [
+                { "name": "meter", "aliases": ["m", ""], "intermediate": false, "dimension": "length" },
+                { "name": "meter", "aliases": ["mtr"], "intermediate": false },
+                { "name": "foot", "aliases": ["ft", "m"], "intermediate": false, "dimension": "lenght" },
+                { "name": "second", "aliases": ["s"], "intermediate": false, "dimension": "time" },
+                { "name": "ft", "aliases": [], "intermediate": false }
+            ],
+            "conversions": [{ "from": "ft", "to": "m", "factor": 0.0 }],
+            "conversions_scale": [
+                { "from": "yd", "to": "m", "factor": 0.9144 },
+                { "from": "s", "to": "m", "factor": 2.0 }
+            ]
+        }"#);
+        assert_eq!(paths(&diagnostics), vec![
+            (Severity::Error, "$.units[0].aliases[1]"),
+            (Severity::Error, "$.units[1].name"),
+            (Severity::Error, "$.units[2].aliases[1]"),
+            (Severity::Error, "$.units[2].dimension"),
+            (Severity::Error, "$.units[4].name"),
+            (Severity::Error, "$.conversions[0].factor"),
+            (Severity::Error, "$.conversions_scale[0].from"),
+            (Severity::Error, "$.conversions_scale[1]"),
+            (Severity::Warning, "$.units[0]"),
+            (Severity::Warning, "$.units[2]"),
+            (Severity::Warning, "$.units[3]"),
+        ]);
+        assert!(diagnostics[1].message().contains("$.units[0]"));
+    }
+
+    #[test]
+    fn test_validate_overwritten_and_inconsistent_edges() {
+        let diagnostics = ConfigValidator::new().validate_json(r#"{
+            "units": [
+                { "name": "meter", "aliases": ["m"], "intermediate": false },
+                { "name": "kilometer", "aliases": ["km"], "intermediate": false },
+                { "name": "mile", "aliases": ["mi"], "intermediate": false }
+            ],
+            "conversions_scale": [
+                { "from": "m", "to": "km", "factor": 0.001 },
+                { "from": "km", "to": "mi", "factor": 0.621371 },
+                { "from": "mi", "to": "m", "factor": 1600.0 },
+                { "from": "km", "to": "m", "factor": 1000.0 }
+            ]
+        }"#);
+        assert_eq!(paths(&diagnostics), vec![
+            (Severity::Warning, "$.conversions_scale[2]"),
+            (Severity::Warning, "$.conversions_scale[3]"),
+        ]);
+        assert!(diagnostics[0].message().contains("mile -> kilometer -> meter"));
+        assert!(diagnostics[1].message().contains("declared at $.conversions_scale[0]"));
+    }
+
+    #[test]
+    fn test_validate_disconnected_groups() {
+        let diagnostics = ConfigValidator::new().validate_json(r#"{
+            "units": [
+                { "name": "meter", "aliases": ["m"], "intermediate": false, "category": "length" },
+                { "name": "kilometer", "aliases": ["km"], "intermediate": false, "category": "length" },
+                { "name": "foot", "aliases": ["ft"], "intermediate": false, "category": "length" },
+                { "name": "inch", "aliases": ["in"], "intermediate": false, "category": "length" },
+                { "name": "gram", "aliases": ["g"], "intermediate": false, "category": "mass" },
+                { "name": "pound", "aliases": ["lb"], "intermediate": false, "category": "mass" }
+            ],
+            "conversions_scale": [
+                { "from": "km", "to": "m", "factor": 1000.0 },
+                { "from": "ft", "to": "in", "factor": 12.0 },
+                { "from": "lb", "to": "g", "factor": 453.59237 }
+            ]
+        }"#);
+        assert_eq!(paths(&diagnostics), vec![(Severity::Warning, "$.units[1]")]);
+        assert_eq!(
+            diagnostics[0].message(),
+            "Units kilometer, meter have no conversions to foot, inch, although they share the category length",
+        );
+    }
+
+    #[test]
+    fn test_validate_agrees_with_from_config() {
+        let configs = [
+            r#"{ "units": [{ "name": "meter", "aliases": ["m", "m"], "intermediate": false }] }"#,
+            r#"{ "units": [{ "name": "meter", "aliases": ["m"], "intermediate": false, "dimension": "L^200" }] }"#,
+            r#"{
+                "units": [
+                    { "name": "meter", "aliases": ["m"], "intermediate": false, "dimension": "length" },
+                    { "name": "second", "aliases": ["s"], "intermediate": false, "dimension": "time" }
+                ],
+                "conversions_scale": [{ "from": "s", "to": "m", "factor": 2.0 }]
+            }"#,
+            r#"{
+                "units": [{ "name": "meter", "aliases": ["m"], "intermediate": false, "prefixable": true }],
+                "conversions_scale": [{ "from": "km", "to": "m", "factor": 1000.0 }]
+            }"#,
+            // only found by lenient lookups, which loading does not use
+            r#"{
+                "units": [
+                    { "name": "Celsius", "aliases": ["°C"], "intermediate": false },
+                    { "name": "Fahrenheit", "aliases": ["°F"], "intermediate": false }
+                ],
+                "conversions": [{ "from": "℃", "to": "°f", "factor": 1.8, "offset": 32.0 }]
+            }"#,
+        ];
+        for json in configs {
+            let config = Config::parse(json, ConfigFormat::Json).unwrap();
+            let errors = ConfigValidator::new().validate(&config).into_iter().any(|d| d.severity() == Severity::Error);
+            assert_eq!(errors, ConversionGraph::from_config(&config).is_err(), "{}", json);
+            let mut lenient = ConversionGraph::new();
+            lenient.set_unit_lookup(UnitLookup::new().with_case_folding(true));
+            assert_eq!(errors, lenient.load_config(&config).is_err(), "{}", json);
+        }
+    }
+
+    #[test]
+    fn test_validate_malformed_json() {
+        let diagnostics = ConfigValidator::new().validate_json(r#"{ "units": [ { "name": 3 } ] }"#);
+        assert_eq!(paths(&diagnostics), vec![(Severity::Error, "$")]);
+    }
+
+    #[test]
+    fn test_validate_duplicate_edge_policy() {
+        let json = r#"{
+            "units": [
+                { "name": "Celsius", "aliases": ["C"], "intermediate": false },
+                { "name": "Fahrenheit", "aliases": ["F"], "intermediate": false }
+            ],
+            "conversions_scale": [{ "from": "C", "to": "F", "factor": 1.8 }],
+            "conversions_offset": [{ "from": "C", "to": "F", "offset": 32.0 }]
+        }"#;
+        let strict = ConfigValidator::new().with_duplicate_edge_policy(DuplicateEdgePolicy::Error);
+        assert_eq!(paths(&strict.validate_json(json)), vec![(Severity::Error, "$.conversions_offset[0]")]);
+        let merge = ConfigValidator::new().with_duplicate_edge_policy(DuplicateEdgePolicy::Merge);
+        assert!(merge.validate_json(json).is_empty());
+    }
+}
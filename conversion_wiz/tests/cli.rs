@@ -0,0 +1,55 @@
+//! End-to-end tests of the command line tool.
+
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+/// A config file in the temporary directory, removed when dropped.
+struct ConfigFile(PathBuf);
+
+impl ConfigFile {
+    fn new(name: &str, content: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("conversion_wiz_cli_{}_{}.json", name, std::process::id()));
+        fs::write(&path, content).unwrap();
+        Self(path)
+    }
+}
+
+impl Drop for ConfigFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+fn run(config: &ConfigFile, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_conversion_wiz"))
+        .arg("--config")
+        .arg(&config.0)
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn test_validate_agrees_with_loading_on_normalized_aliases() {
+    // `℃` only matches `°C` after Unicode normalization
+    let config = ConfigFile::new("normalized", r#"{
+        "units": [
+            { "name": "Celsius", "aliases": ["°C"], "intermediate": false },
+            { "name": "Fahrenheit", "aliases": ["°F"], "intermediate": false }
+        ],
+        "conversions": [{ "from": "℃", "to": "°F", "factor": 1.8, "offset": 32.0 }]
+    }"#);
+    let validate = run(&config, &["validate"]);
+    assert_eq!(validate.status.code(), Some(7));
+    assert!(String::from_utf8_lossy(&validate.stdout).contains("$.conversions[0].from"));
+    let convert = run(&config, &["convert", "100", "°C", "°F"]);
+    assert_eq!(convert.status.code(), Some(7));
+
+    // queries are still looked up leniently once the config loads
+    let config = ConfigFile::new("exact", &fs::read_to_string(&config.0).unwrap().replace("℃", "°C"));
+    assert_eq!(run(&config, &["validate"]).status.code(), Some(0));
+    let convert = run(&config, &["convert", "100", "℃", "°F"]);
+    assert_eq!(convert.status.code(), Some(0));
+    assert!(String::from_utf8_lossy(&convert.stdout).contains("212"));
+}